@@ -0,0 +1,510 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::greedy;
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+use crate::observer::{Event, NullObserver, Observer};
+use crate::solver::LocalSearch;
+use crate::two_opt::{self, NEIGHBORS};
+use std::time::Instant;
+
+// Weight of pheromone by default
+pub const ALPHA: f64 = 1.;
+// Weight of closeness by default
+pub const BETA: f64 = 2.;
+// Number of ants building a tour in each iteration by default
+pub const ANTS: usize = 10;
+// Probability that an ant of ACS goes to the best city instead of a random one by default
+pub const Q0: f64 = 0.9;
+// Number of iterations by default
+pub const ITERATIONS: usize = 1000;
+// Rate at which ACS moves pheromone of edges an ant walks back to the initial amount
+const XI: f64 = 0.1;
+
+// How pheromone is updated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    // Ant Colony System. Ants go to the best city with probability q0. Pheromone of
+    // an edge evaporates towards the initial amount when an ant walks it, and only edges
+    // of the best tour found evaporate and receive pheromone after each iteration.
+    #[default]
+    AntColonySystem,
+    // MAX-MIN Ant System. All edges evaporate and edges of the shortest tour of the iteration
+    // receive pheromone. Pheromone is kept between tau_max = 1 / (rho * best length)
+    // and tau_min = tau_max / (2 * number of cities) and starts from tau_max.
+    MaxMinAntSystem,
+}
+
+impl Variant {
+    // Evaporation rate used when rho is not given
+    pub fn default_rho(&self) -> f64 {
+        match self {
+            Variant::AntColonySystem => 0.1,
+            Variant::MaxMinAntSystem => 0.02,
+        }
+    }
+}
+
+// Ant colony optimization. Each ant builds a tour from a random city, going to a city
+// not visited yet among the nearest neighbors of the current city with probability
+// proportional to pheromone^alpha * (1 / distance)^beta, or to the city with the largest
+// weight among all cities not visited yet if no neighbor is left. Pheromone is kept for
+// all edges greedy::edges enumerates and starts from the length of the given tour.
+// Tours of ants are improved by 2-opt when two_opt is set. The shortest tour found is
+// returned, and the same seed gives the same tour.
+#[derive(Debug, Clone)]
+pub struct AntColony {
+    variant: Variant,
+    alpha: f64,
+    beta: f64,
+    rho: Option<f64>,
+    q0: f64,
+    ants: usize,
+    iterations: usize,
+    neighbors: usize,
+    two_opt: bool,
+}
+
+impl Default for AntColony {
+    fn default() -> AntColony {
+        AntColony {
+            variant: Variant::default(),
+            alpha: ALPHA,
+            beta: BETA,
+            rho: None,
+            q0: Q0,
+            ants: ANTS,
+            iterations: ITERATIONS,
+            neighbors: NEIGHBORS,
+            two_opt: false,
+        }
+    }
+}
+
+impl AntColony {
+    pub fn new() -> AntColony {
+        AntColony::default()
+    }
+
+    pub fn variant(mut self, variant: Variant) -> AntColony {
+        self.variant = variant;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: f64) -> AntColony {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn beta(mut self, beta: f64) -> AntColony {
+        self.beta = beta;
+        self
+    }
+
+    // Evaporation rate. Without it Variant::default_rho is used.
+    pub fn rho(mut self, rho: f64) -> AntColony {
+        self.rho = Some(rho);
+        self
+    }
+
+    // Probability that an ant of ACS goes to the best city. MMAS ignores it.
+    pub fn q0(mut self, q0: f64) -> AntColony {
+        self.q0 = q0;
+        self
+    }
+
+    pub fn ants(mut self, ants: usize) -> AntColony {
+        self.ants = ants.max(1);
+        self
+    }
+
+    // Number of iterations. The deadline of the pipeline also stops the search.
+    pub fn iterations(mut self, iterations: usize) -> AntColony {
+        self.iterations = iterations;
+        self
+    }
+
+    // Number of nearest neighbors of each city ants go to first
+    pub fn neighbors(mut self, k: usize) -> AntColony {
+        self.neighbors = k;
+        self
+    }
+
+    // Whether tours of ants are improved by 2-opt
+    pub fn two_opt(mut self, two_opt: bool) -> AntColony {
+        self.two_opt = two_opt;
+        self
+    }
+
+    // Run the colony and leave the shortest tour found in cities_idx.
+    // Returns the number of iterations.
+    pub fn run<M: Metric + ?Sized, R: Rng>(
+        &self,
+        observer: &mut dyn Observer,
+        metric: &M,
+        cities_idx: &mut [usize],
+        rng: &mut R,
+        deadline: Option<Instant>,
+    ) -> usize {
+        let city_len = cities_idx.len();
+        let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if city_len < 4 {
+            return 0;
+        }
+
+        let neighbors = NeighborLists::new(metric, self.neighbors);
+        let rho = self.rho.unwrap_or(self.variant.default_rho());
+        let mut best_cost = metric.tour_length(cities_idx);
+        let mut colony = Colony::new(self, metric, &neighbors, best_cost);
+
+        let mut iterations = 0;
+        while iterations < self.iterations && !expired() {
+            iterations += 1;
+
+            let mut iteration_best: Option<(Cost, Vec<usize>)> = None;
+            for _ in 0..self.ants {
+                let start = rng.gen_range(0..city_len);
+                let mut tour = colony.walk(start, rng);
+                if self.two_opt {
+                    two_opt::local_optimum(
+                        &mut NullObserver,
+                        metric,
+                        &mut tour,
+                        &neighbors,
+                        false,
+                        deadline,
+                    );
+                }
+                let cost = metric.tour_length(&tour);
+                if iteration_best.as_ref().is_none_or(|(c, _)| cost < *c) {
+                    iteration_best = Some((cost, tour));
+                }
+            }
+
+            let (cost, tour) = iteration_best.unwrap();
+            if cost < best_cost {
+                best_cost = cost;
+                cities_idx.copy_from_slice(&tour);
+                observer.notify(&Event::NewBest {
+                    cities_idx,
+                    cost: best_cost,
+                });
+            }
+
+            match self.variant {
+                Variant::AntColonySystem => colony.deposit(cities_idx, best_cost, rho, false),
+                Variant::MaxMinAntSystem => {
+                    colony.evaporate(rho);
+                    colony.deposit(&tour, cost, rho, true);
+                    colony.clamp(best_cost, rho);
+                }
+            }
+        }
+
+        iterations
+    }
+}
+
+impl LocalSearch for AntColony {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        rng: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        self.run(observer, metric, cities_idx, rng, deadline);
+    }
+}
+
+// Pheromone and closeness of all edges
+struct Colony<'a> {
+    config: &'a AntColony,
+    neighbors: &'a NeighborLists,
+    city_len: usize,
+    pheromone: Vec<f64>,
+    // (1 / distance)^beta
+    closeness: Vec<f64>,
+    // Initial pheromone
+    tau0: f64,
+}
+
+impl<'a> Colony<'a> {
+    fn new<M: Metric + ?Sized>(
+        config: &'a AntColony,
+        metric: &M,
+        neighbors: &'a NeighborLists,
+        cost: Cost,
+    ) -> Colony<'a> {
+        let city_len = metric.len();
+        let rho = config.rho.unwrap_or(config.variant.default_rho());
+        let tau0 = match config.variant {
+            Variant::AntColonySystem => 1. / (city_len as f64 * cost as f64),
+            Variant::MaxMinAntSystem => 1. / (rho * cost as f64),
+        };
+
+        let mut pheromone = vec![0.; city_len * city_len];
+        let mut closeness = vec![0.; city_len * city_len];
+        for (dist, i, j) in greedy::edges(metric) {
+            // Cities at the same place are treated as distance 1 apart
+            let eta = (1. / dist.max(1) as f64).powf(config.beta);
+            for (a, b) in [(i, j), (j, i)] {
+                pheromone[a * city_len + b] = tau0;
+                closeness[a * city_len + b] = eta;
+            }
+        }
+
+        Colony {
+            config,
+            neighbors,
+            city_len,
+            pheromone,
+            closeness,
+            tau0,
+        }
+    }
+
+    fn weight(&self, a: usize, b: usize) -> f64 {
+        let k = a * self.city_len + b;
+        self.pheromone[k].powf(self.config.alpha) * self.closeness[k]
+    }
+
+    // Tour of an ant from start
+    fn walk<R: Rng>(&mut self, start: usize, rng: &mut R) -> Vec<usize> {
+        let acs = self.config.variant == Variant::AntColonySystem;
+        let mut visited = vec![false; self.city_len];
+        let mut tour = Vec::with_capacity(self.city_len);
+        let mut city = start;
+        visited[city] = true;
+        tour.push(city);
+
+        while tour.len() < self.city_len {
+            let next = self.next_city(city, &visited, acs && rng.gen_bool(self.config.q0), rng);
+            if acs {
+                self.local_update(city, next);
+            }
+            city = next;
+            visited[city] = true;
+            tour.push(city);
+        }
+        if acs {
+            self.local_update(city, start);
+        }
+        tour
+    }
+
+    // Neighbor not visited yet chosen by the largest weight if greedy is set or with
+    // probability proportional to the weight otherwise, or the city with the largest weight
+    // among all cities not visited yet if no neighbor is left
+    fn next_city<R: Rng>(&self, city: usize, visited: &[bool], greedy: bool, rng: &mut R) -> usize {
+        let candidates = self
+            .neighbors
+            .get(city)
+            .iter()
+            .copied()
+            .filter(|c| !visited[*c])
+            .map(|c| (c, self.weight(city, c)))
+            .collect::<Vec<(usize, f64)>>();
+
+        if !candidates.is_empty() && !greedy {
+            let total = candidates.iter().map(|(_, w)| w).sum::<f64>();
+            let mut r = rng.gen::<f64>() * total;
+            for (c, w) in candidates.iter() {
+                if r < *w {
+                    return *c;
+                }
+                r -= w;
+            }
+            // Rounding errors
+            return candidates.last().unwrap().0;
+        }
+
+        // The first city with the largest weight so ties go to the nearer one
+        let best = |best: Option<(usize, f64)>, (c, w): (usize, f64)| match best {
+            Some((_, b)) if b >= w => best,
+            _ => Some((c, w)),
+        };
+        match candidates.into_iter().fold(None, best) {
+            Some((c, _)) => c,
+            None => {
+                (0..self.city_len)
+                    .filter(|c| !visited[*c])
+                    .map(|c| (c, self.weight(city, c)))
+                    .fold(None, best)
+                    .unwrap()
+                    .0
+            }
+        }
+    }
+
+    fn set(&mut self, a: usize, b: usize, tau: f64) {
+        self.pheromone[a * self.city_len + b] = tau;
+        self.pheromone[b * self.city_len + a] = tau;
+    }
+
+    // Move pheromone of an edge an ant walked towards tau0
+    fn local_update(&mut self, a: usize, b: usize) {
+        let tau = self.pheromone[a * self.city_len + b];
+        self.set(a, b, (1. - XI) * tau + XI * self.tau0);
+    }
+
+    fn evaporate(&mut self, rho: f64) {
+        for tau in self.pheromone.iter_mut() {
+            *tau *= 1. - rho;
+        }
+    }
+
+    // Add pheromone 1 / cost to edges of the tour. Unless all edges are already evaporated,
+    // they evaporate first and receive rho / cost instead as ACS does.
+    fn deposit(&mut self, tour: &[usize], cost: Cost, rho: f64, evaporated: bool) {
+        let amount = 1. / cost as f64;
+        for k in 0..tour.len() {
+            let (a, b) = (tour[k], tour[(k + 1) % tour.len()]);
+            let tau = self.pheromone[a * self.city_len + b];
+            let tau = if evaporated {
+                tau + amount
+            } else {
+                (1. - rho) * tau + rho * amount
+            };
+            self.set(a, b, tau);
+        }
+    }
+
+    // Keep pheromone between the limits of MMAS
+    fn clamp(&mut self, best_cost: Cost, rho: f64) {
+        let tau_max = 1. / (rho * best_cost as f64);
+        let tau_min = tau_max / (2. * self.city_len as f64);
+        for i in 0..self.city_len {
+            for j in 0..self.city_len {
+                if i != j {
+                    let k = i * self.city_len + j;
+                    self.pheromone[k] = self.pheromone[k].clamp(tau_min, tau_max);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{TSP_FILE_BERLIN52, TSP_FILE_KROC100},
+        greedy::Greedy,
+        nearest_neighbor,
+        solver::{Pipeline, Solver},
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    #[test]
+    fn pheromone() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+
+        for variant in [Variant::AntColonySystem, Variant::MaxMinAntSystem] {
+            let config = AntColony::new().variant(variant);
+            let colony = Colony::new(&config, &instance, &neighbors, 10000);
+
+            // Every edge greedy enumerates starts with the same pheromone
+            for (_, i, j) in greedy::edges(&instance) {
+                assert_eq!(colony.pheromone[i * 52 + j], colony.tau0);
+                assert_eq!(colony.pheromone[j * 52 + i], colony.tau0);
+            }
+            assert!(colony.tau0 > 0.);
+        }
+    }
+
+    // An ACS ant always taking the closest city is the nearest neighbor method
+    #[test]
+    fn walk() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let config = AntColony::new().alpha(0.).q0(1.);
+        let mut colony = Colony::new(&config, &instance, &neighbors, 30000);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let tour = colony.walk(0, &mut rng);
+        assert_eq!(tour, nearest_neighbor::solver(&mut NullObserver, &instance));
+
+        // Walked edges lose pheromone towards tau0 and the others keep it
+        colony.pheromone.iter_mut().for_each(|tau| *tau = 1.);
+        let tour = colony.walk(5, &mut rng);
+        assert_eq!(tour[0], 5);
+        let walked = (0..100)
+            .filter(|k| colony.pheromone[tour[*k] * 100 + tour[(k + 1) % 100]] < 1.)
+            .count();
+        assert_eq!(walked, 100);
+        assert_eq!(colony.pheromone[tour[0] * 100 + tour[2]], 1.);
+
+        // Random ants also visit every city once
+        let config = AntColony::new().variant(Variant::MaxMinAntSystem);
+        let mut colony = Colony::new(&config, &instance, &neighbors, 30000);
+        for start in 0..100 {
+            let mut tour = colony.walk(start, &mut rng);
+            tour.sort();
+            assert_eq!(tour, (0..100).collect::<Vec<usize>>());
+        }
+    }
+
+    #[test]
+    fn variants() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let greedy = Pipeline::new(Greedy).solve(&instance);
+        let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+
+        for variant in [Variant::AntColonySystem, Variant::MaxMinAntSystem] {
+            // Ants alone find tours shorter than greedy
+            let ants = Pipeline::new(Greedy)
+                .then(AntColony::new().variant(variant).iterations(200))
+                .seed(0)
+                .solve(&instance);
+            // and 2-opt on their tours goes past the 2-opt local optimum
+            let with_two_opt = Pipeline::new(Greedy)
+                .then(
+                    AntColony::new()
+                        .variant(variant)
+                        .iterations(50)
+                        .two_opt(true),
+                )
+                .seed(0)
+                .solve(&instance);
+
+            println!(
+                "{:?}: greedy {} ants {} 2-opt {} ants+2-opt {}",
+                variant, greedy.cost, ants.cost, two_opt.cost, with_two_opt.cost
+            );
+            assert!(ants.cost < greedy.cost);
+            assert!(with_two_opt.cost < two_opt.cost);
+            assert_eq!(ants.cost, instance.tour_length(&ants.cities));
+        }
+    }
+
+    #[test]
+    fn seeded() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let solve = |seed: u64| {
+            Pipeline::new(Greedy)
+                .then(AntColony::new().iterations(20))
+                .seed(seed)
+                .solve(&instance)
+        };
+        assert_eq!(solve(3), solve(3));
+    }
+
+    #[test]
+    fn deadline() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let start = Instant::now();
+        let solution = Pipeline::new(Greedy)
+            .then(AntColony::new().iterations(usize::MAX).two_opt(true))
+            .time_limit(Duration::from_millis(200))
+            .solve(&instance);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+    }
+}
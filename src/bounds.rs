@@ -0,0 +1,267 @@
+use crate::greedy;
+use crate::matrix::DistanceMatrix;
+use crate::metric::{Cost, Metric};
+use crate::unionfind::UnionFind;
+
+// Number of subgradient steps of the Held-Karp bound
+pub const ITERATIONS: usize = 1000;
+
+// Subgradient steps taking about as long as ITERATIONS steps on 300 cities, since each
+// step takes O(n^2) time. Larger instances get fewer steps and a lower bound.
+pub fn iterations(city_len: usize) -> usize {
+    (ITERATIONS * 300 * 300 / city_len.max(1).pow(2)).clamp(1, ITERATIONS)
+}
+
+// Lower bounds of the optimal tour length of symmetric instances. Each is at most
+// the optimum and they usually rise in this order:
+// mst < one_tree < held_karp <= optimum.
+// With three or less cities every tour is optimal and its length is returned.
+
+// Length of the minimum spanning tree by Kruskal's algorithm.
+// Removing an edge from a tour makes a spanning tree.
+pub fn mst<M: Metric + ?Sized>(metric: &M) -> Cost {
+    let city_len = metric.len();
+    if city_len <= 3 {
+        return metric.tour_length(&(0..city_len).collect::<Vec<usize>>());
+    }
+    spanning_tree(&greedy::edges(metric), city_len, None)
+}
+
+// Largest 1-tree over the choice of the special city. A 1-tree is a spanning tree of
+// cities other than the special one plus its two shortest edges, and every tour is one.
+pub fn one_tree<M: Metric + ?Sized>(metric: &M) -> Cost {
+    let city_len = metric.len();
+    if city_len <= 3 {
+        return metric.tour_length(&(0..city_len).collect::<Vec<usize>>());
+    }
+
+    let edges = greedy::edges(metric);
+    (0..city_len)
+        .map(|special| {
+            let tree = spanning_tree(&edges, city_len, Some(special));
+            // Edges are sorted so the first two of the special city are the shortest
+            let two = edges
+                .iter()
+                .filter(|(_, i, j)| *i == special || *j == special)
+                .take(2)
+                .map(|(d, ..)| d)
+                .sum::<Cost>();
+            tree + two
+        })
+        .max()
+        .unwrap()
+}
+
+// Held-Karp bound by subgradient optimization of 1-trees at city 0. Penalties added
+// to both ends of edges don't change which tour is optimal, but subtracting twice
+// their sum from a 1-tree gives another bound. Penalties of cities with three or
+// more edges are raised and those of leaves are lowered to make the 1-tree a tour.
+// upper_bound is the length of any tour, which scales the steps.
+pub fn held_karp<M: Metric + ?Sized>(metric: &M, upper_bound: Cost, iterations: usize) -> Cost {
+    let city_len = metric.len();
+    if city_len <= 3 {
+        return metric.tour_length(&(0..city_len).collect::<Vec<usize>>());
+    }
+
+    let matrix = DistanceMatrix::from_metric(metric);
+    let mut penalties = vec![0.; city_len];
+    let mut best = f64::MIN;
+    // Step size factor halved when the bound does not rise for a while
+    let mut factor = 2.;
+    let mut since_rise = 0;
+    for _ in 0..iterations.max(1) {
+        let (bound, degree) = penalized_one_tree(&matrix, &penalties);
+        if bound > best {
+            best = bound;
+            since_rise = 0;
+        } else {
+            since_rise += 1;
+            if since_rise >= city_len / 2 {
+                factor /= 2.;
+                since_rise = 0;
+            }
+        }
+
+        let norm = degree.iter().map(|d| (*d as f64 - 2.).powi(2)).sum::<f64>();
+        // The 1-tree is a tour, which is optimal
+        if norm == 0. {
+            break;
+        }
+        let step = factor * (upper_bound as f64 - bound).max(1.) / norm;
+        for (penalty, d) in penalties.iter_mut().zip(degree.iter()) {
+            *penalty += step * (*d as f64 - 2.);
+        }
+    }
+    round_up(best).min(upper_bound)
+}
+
+// Relative gap (cost - lower_bound) / lower_bound of a tour, 0 if the tour is optimal.
+// It is infinite when the bound is not positive.
+pub fn gap(cost: Cost, lower_bound: Cost) -> f64 {
+    if cost <= lower_bound {
+        return 0.;
+    }
+    if lower_bound <= 0 {
+        return f64::INFINITY;
+    }
+    (cost - lower_bound) as f64 / lower_bound as f64
+}
+
+// Smallest integer bound, since tour lengths are integers
+pub(crate) fn round_up(bound: f64) -> Cost {
+    // Rounding errors of penalties must not raise the bound above the optimum
+    (bound - 1e-6).ceil() as Cost
+}
+
+// Length of the minimum spanning tree of sorted edges leaving out excluded
+fn spanning_tree(edges: &[(Cost, usize, usize)], city_len: usize, excluded: Option<usize>) -> Cost {
+    let mut uf = UnionFind::new(city_len);
+    let mut length = 0;
+    let mut count = 0;
+    let target = city_len - 1 - excluded.map_or(0, |_| 1);
+    for (d, i, j) in edges.iter() {
+        if count == target {
+            break;
+        }
+        if excluded.is_some_and(|k| *i == k || *j == k) || uf.same(*i, *j) {
+            continue;
+        }
+        uf.unite(*i, *j);
+        length += d;
+        count += 1;
+    }
+    length
+}
+
+// Bound and degrees of the 1-tree at city 0 under penalties. Penalties change the
+// order of edges at every step, so Prim's O(n^2) algorithm is used instead of sorting.
+fn penalized_one_tree(matrix: &DistanceMatrix, penalties: &[f64]) -> (f64, Vec<usize>) {
+    let city_len = matrix.dimension();
+    let cost = |i: usize, j: usize| matrix.get(i, j) as f64 + penalties[i] + penalties[j];
+
+    let mut degree = vec![0; city_len];
+    let mut length = 0.;
+
+    // Prim's algorithm on cities 1..n starting from city 1
+    let mut in_tree = vec![false; city_len];
+    let mut nearest = vec![(f64::INFINITY, 1); city_len];
+    let mut city = 1;
+    in_tree[1] = true;
+    for _ in 2..city_len {
+        let mut next = 0;
+        for j in 2..city_len {
+            if in_tree[j] {
+                continue;
+            }
+            if cost(city, j) < nearest[j].0 {
+                nearest[j] = (cost(city, j), city);
+            }
+            if next == 0 || nearest[j].0 < nearest[next].0 {
+                next = j;
+            }
+        }
+        let (d, parent) = nearest[next];
+        in_tree[next] = true;
+        length += d;
+        degree[next] += 1;
+        degree[parent] += 1;
+        city = next;
+    }
+
+    // Two shortest edges of city 0
+    let mut zero = (1..city_len)
+        .map(|j| (cost(0, j), j))
+        .collect::<Vec<(f64, usize)>>();
+    zero.select_nth_unstable_by(1, |a, b| a.0.total_cmp(&b.0));
+    for (d, j) in zero.into_iter().take(2) {
+        length += d;
+        degree[0] += 1;
+        degree[j] += 1;
+    }
+
+    (length - 2. * penalties.iter().sum::<f64>(), degree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225},
+        held_karp,
+        metric::{CoordMetric, EdgeWeightType, FnMetric},
+        tsplib::Instance,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn tiny() {
+        // Path 0 - 1 - 2 - 3 of length 1 edges, other edges are 2
+        let metric = FnMetric::new(4, |i, j| if i.abs_diff(j) == 1 { 1 } else { 2 });
+        assert_eq!(mst(&metric), 3);
+        // Tour 0 - 1 - 2 - 3 - 0 is a 1-tree
+        assert_eq!(one_tree(&metric), 5);
+        assert_eq!(held_karp(&metric, 5, ITERATIONS), 5);
+
+        let metric = FnMetric::new(3, |i, j| (i + j) as Cost);
+        assert_eq!(mst(&metric), 1 + 2 + 3);
+        assert_eq!(one_tree(&metric), 1 + 2 + 3);
+        assert_eq!(held_karp(&metric, 6, ITERATIONS), 1 + 2 + 3);
+    }
+
+    #[test]
+    fn below_optimum() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for city_len in 4..=14 {
+            let coords = (0..city_len)
+                .map(|_| (rng.gen_range(0. ..1000.), rng.gen_range(0. ..1000.)))
+                .collect();
+            let metric = CoordMetric::new(coords, EdgeWeightType::Euc2d);
+            let optimum = held_karp::solver(&metric).cost;
+
+            let (mst, one_tree) = (mst(&metric), one_tree(&metric));
+            let held_karp = held_karp(&metric, optimum, ITERATIONS);
+            assert!(mst <= one_tree, "{} cities", city_len);
+            assert!(one_tree <= optimum, "{} cities", city_len);
+            assert!(held_karp <= optimum, "{} cities", city_len);
+            // Subgradient steps get close to the optimum on small instances
+            assert!(gap(optimum, held_karp) < 0.02, "{} cities", city_len);
+        }
+    }
+
+    #[test]
+    fn instances() {
+        // Optimum and the largest gap of the Held-Karp bound from it
+        for (file, optimum, max_gap) in [
+            (TSP_FILE_BERLIN52, 7542, 0.001),
+            (TSP_FILE_KROC100, 20749, 0.02),
+            // ts225 is known for its large gap
+            (TSP_FILE_TS225, 126643, 0.1),
+        ] {
+            let instance = Instance::load(file).unwrap();
+            let (mst, one_tree) = (mst(&instance), one_tree(&instance));
+            let held_karp = held_karp(&instance, optimum, ITERATIONS);
+            println!("{}: {} {} {}", file, mst, one_tree, held_karp);
+            assert!(mst < one_tree, "{}", file);
+            assert!(one_tree < held_karp, "{}", file);
+            assert!(held_karp <= optimum, "{}", file);
+            assert!(gap(optimum, held_karp) < max_gap, "{}", file);
+        }
+    }
+
+    #[test]
+    fn budget() {
+        assert_eq!(iterations(52), ITERATIONS);
+        assert_eq!(iterations(300), ITERATIONS);
+        assert_eq!(iterations(3000), ITERATIONS / 100);
+        assert_eq!(iterations(100_000), 1);
+    }
+
+    #[test]
+    fn gaps() {
+        assert_eq!(gap(110, 100), 0.1);
+        assert_eq!(gap(100, 100), 0.);
+        assert_eq!(gap(100, 0), f64::INFINITY);
+        assert_eq!(round_up(99.2), 100);
+        assert_eq!(round_up(100. + 1e-9), 100);
+    }
+}
@@ -0,0 +1,628 @@
+use crate::bounds::{self, round_up};
+use crate::greedy::Greedy;
+use crate::lin_kernighan::LinKernighan;
+use crate::matrix::DistanceMatrix;
+use crate::metric::{Cost, Metric};
+use crate::nearest_insertion::NearestInsertion;
+use crate::nearest_neighbor::NearestNeighbor;
+use crate::observer::{Event, Observer};
+use crate::solver::{ConstructionHeuristic, Pipeline, Solution, Solver};
+use crate::unionfind::UnionFind;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+// Number of subgradient steps at the root, where the penalties start from zero
+pub const ROOT_ITERATIONS: usize = 1000;
+// Number of subgradient steps at other nodes, which start from the penalties of the parent
+pub const NODE_ITERATIONS: usize = 50;
+
+// Tour found by branch-and-bound with a lower bound of the optimal length.
+// The tour is optimal when lower_bound equals its cost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundedSolution {
+    pub solution: Solution,
+    pub lower_bound: Cost,
+    // Number of nodes of the search tree whose bound was computed
+    pub nodes: usize,
+}
+
+impl BoundedSolution {
+    pub fn is_optimal(&self) -> bool {
+        self.lower_bound >= self.solution.cost
+    }
+
+    // (tour length - lower bound) / lower bound, which is 0 when the tour is optimal
+    pub fn gap(&self) -> f64 {
+        bounds::gap(self.solution.cost, self.lower_bound)
+    }
+}
+
+// Branch-and-bound for symmetric instances bounded by Held-Karp 1-trees. A 1-tree is
+// a minimum spanning tree of cities other than city 0 plus the two shortest edges of
+// city 0, and every tour is a 1-tree. Subgradient optimization adds penalties to cities
+// so the 1-tree gets closer to a tour, which raises the bound. Nodes are searched
+// best first and branch on a city with three or more edges in its 1-tree by excluding
+// or including its edges. The upper bound starts from the shortest tour of greedy,
+// nearest neighbor and nearest insertion improved by Lin-Kernighan unless a tour is
+// given. When the node or time limit stops the search, the best tour found is returned
+// with the smallest bound of the remaining nodes. 1-trees do not bound tours of asymmetric
+// metrics, so they are not searched and the bound is the sum of the shortest edges
+// leaving each city.
+#[derive(Debug, Clone, Default)]
+pub struct BranchAndBound {
+    node_limit: Option<usize>,
+    time_limit: Option<Duration>,
+    initial_tour: Option<Vec<usize>>,
+}
+
+impl BranchAndBound {
+    pub fn new() -> BranchAndBound {
+        BranchAndBound::default()
+    }
+
+    pub fn node_limit(mut self, nodes: usize) -> BranchAndBound {
+        self.node_limit = Some(nodes);
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> BranchAndBound {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    // Tour giving the first upper bound instead of heuristics
+    pub fn initial_tour(mut self, cities_idx: Vec<usize>) -> BranchAndBound {
+        self.initial_tour = Some(cities_idx);
+        self
+    }
+
+    pub fn solve<M: Metric + ?Sized>(&self, metric: &M) -> BoundedSolution {
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        let city_len = metric.len();
+
+        let cities = match &self.initial_tour {
+            Some(cities_idx) => cities_idx.clone(),
+            None if city_len <= 3 => (0..city_len).collect(),
+            None => heuristic_tour(&DistanceMatrix::from_metric(metric)),
+        };
+        let cost = metric.tour_length(&cities);
+        let mut best = Solution {
+            cities,
+            cost,
+            seed: None,
+        };
+        if !metric.is_symmetric() {
+            // Every tour leaves each city once
+            let lower_bound = (0..city_len)
+                .map(|i| {
+                    (0..city_len)
+                        .filter(|j| *j != i)
+                        .map(|j| metric.dist(i, j))
+                        .min()
+                        .unwrap_or(0)
+                })
+                .sum();
+            return BoundedSolution {
+                solution: best,
+                lower_bound,
+                nodes: 0,
+            };
+        }
+        if city_len <= 3 {
+            return BoundedSolution {
+                lower_bound: best.cost,
+                solution: best,
+                nodes: 0,
+            };
+        }
+
+        let mut search = Search::new(DistanceMatrix::from_metric(metric));
+        let mut queue = BinaryHeap::new();
+        queue.push(Node {
+            bound: f64::MIN,
+            penalties: vec![0.; city_len],
+            constraints: vec![],
+        });
+
+        let mut nodes = 0;
+        while let Some(node) = queue.peek() {
+            if round_up(node.bound) >= best.cost {
+                // Every other node is bounded by the same or a larger value
+                queue.clear();
+                break;
+            }
+            if self.node_limit.is_some_and(|limit| nodes >= limit)
+                || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                break;
+            }
+
+            let node = queue.pop().unwrap();
+            let iterations = if nodes == 0 {
+                ROOT_ITERATIONS
+            } else {
+                NODE_ITERATIONS
+            };
+            nodes += 1;
+            for child in search.branch(node, iterations, &mut best) {
+                queue.push(child);
+            }
+        }
+
+        let lower_bound = match queue.peek() {
+            None => best.cost,
+            // The root was not processed and has no bound yet, so take a single 1-tree
+            Some(_) if nodes == 0 => bounds::held_karp(metric, best.cost, 1),
+            Some(node) => round_up(node.bound).min(best.cost),
+        };
+        BoundedSolution {
+            solution: best,
+            lower_bound,
+            nodes,
+        }
+    }
+}
+
+// Exact solver usable as the constructor of a pipeline.
+// The lower bound is reported by Event::LowerBound for symmetric metrics.
+impl ConstructionHeuristic for BranchAndBound {
+    fn construct(&mut self, metric: &dyn Metric, observer: &mut dyn Observer) -> Vec<usize> {
+        let result = self.solve(metric);
+        if metric.is_symmetric() {
+            observer.notify(&Event::LowerBound {
+                lower_bound: result.lower_bound,
+                cost: result.solution.cost,
+            });
+        }
+        result.solution.cities
+    }
+}
+
+// Shortest of the tours of the constructors improved by Lin-Kernighan
+fn heuristic_tour(matrix: &DistanceMatrix) -> Vec<usize> {
+    let pipelines = [
+        Pipeline::new(Greedy),
+        Pipeline::new(NearestNeighbor),
+        Pipeline::new(NearestInsertion),
+    ];
+    pipelines
+        .into_iter()
+        .map(|pipeline| pipeline.then(LinKernighan::new()).seed(0).solve(matrix))
+        .min_by_key(|solution| solution.cost)
+        .unwrap()
+        .cities
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EdgeState {
+    Free,
+    Included,
+    Excluded,
+}
+
+// Node of the search tree with the bound and penalties of its parent
+#[derive(Debug, Clone)]
+struct Node {
+    bound: f64,
+    penalties: Vec<f64>,
+    // Edges included or excluded from the root
+    constraints: Vec<(usize, usize, EdgeState)>,
+}
+
+// BinaryHeap pops the node with the smallest bound first
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        other.bound.total_cmp(&self.bound)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+// 1-tree under penalties
+#[derive(Debug, Clone)]
+struct OneTree {
+    // Length with penalties minus twice the sum of penalties, which is a lower bound
+    bound: f64,
+    edges: Vec<(usize, usize)>,
+    degree: Vec<usize>,
+}
+
+struct Search {
+    matrix: DistanceMatrix,
+    city_len: usize,
+    // State of each edge at the current node
+    state: Vec<EdgeState>,
+}
+
+impl Search {
+    fn new(matrix: DistanceMatrix) -> Search {
+        let city_len = matrix.dimension();
+        Search {
+            matrix,
+            city_len,
+            state: vec![EdgeState::Free; city_len * city_len],
+        }
+    }
+
+    fn get(&self, i: usize, j: usize) -> EdgeState {
+        self.state[i * self.city_len + j]
+    }
+
+    fn set(&mut self, i: usize, j: usize, state: EdgeState) {
+        self.state[i * self.city_len + j] = state;
+        self.state[j * self.city_len + i] = state;
+    }
+
+    // Set the edge states of the node. A city with two included edges cannot have
+    // any other edge. Returns false if a city has more than two included edges.
+    fn restore(&mut self, constraints: &[(usize, usize, EdgeState)]) -> bool {
+        self.state.fill(EdgeState::Free);
+        for (i, j, state) in constraints.iter() {
+            self.set(*i, *j, *state);
+        }
+
+        for i in 0..self.city_len {
+            let included = (0..self.city_len)
+                .filter(|j| self.get(i, *j) == EdgeState::Included)
+                .count();
+            if included > 2 {
+                return false;
+            }
+            if included == 2 {
+                for j in 0..self.city_len {
+                    if j != i && self.get(i, j) == EdgeState::Free {
+                        self.set(i, j, EdgeState::Excluded);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // Minimum 1-tree under penalties respecting the edge states, or None if none exists
+    fn one_tree(&self, penalties: &[f64]) -> Option<OneTree> {
+        let n = self.city_len;
+        let cost = |i: usize, j: usize| self.matrix.get(i, j) as f64 + penalties[i] + penalties[j];
+
+        let mut edges = vec![];
+        let mut degree = vec![0; n];
+        let mut length = 0.;
+        let mut add = |i: usize, j: usize, edges: &mut Vec<(usize, usize)>| {
+            edges.push((i, j));
+            degree[i] += 1;
+            degree[j] += 1;
+            length += cost(i, j);
+        };
+
+        // Kruskal on cities other than 0 starting from the included edges
+        let mut uf = UnionFind::new(n);
+        let mut free = vec![];
+        for i in 1..n {
+            for j in i + 1..n {
+                match self.get(i, j) {
+                    EdgeState::Included => {
+                        if uf.same(i, j) {
+                            // Included edges make a cycle without city 0
+                            return None;
+                        }
+                        uf.unite(i, j);
+                        add(i, j, &mut edges);
+                    }
+                    EdgeState::Free => free.push((cost(i, j), i, j)),
+                    EdgeState::Excluded => {}
+                }
+            }
+        }
+        free.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, i, j) in free {
+            if edges.len() == n - 2 {
+                break;
+            }
+            if !uf.same(i, j) {
+                uf.unite(i, j);
+                add(i, j, &mut edges);
+            }
+        }
+        if edges.len() != n - 2 {
+            return None;
+        }
+
+        // Two edges of city 0, included ones first
+        let mut zero = (1..n)
+            .filter(|j| self.get(0, *j) != EdgeState::Excluded)
+            .map(|j| (self.get(0, j) != EdgeState::Included, cost(0, j), j))
+            .collect::<Vec<(bool, f64, usize)>>();
+        zero.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        if zero.len() < 2 || zero.get(2).is_some_and(|(free, ..)| !free) {
+            return None;
+        }
+        for (_, _, j) in zero.into_iter().take(2) {
+            add(0, j, &mut edges);
+        }
+
+        let bound = length - 2. * penalties.iter().sum::<f64>();
+        Some(OneTree {
+            bound,
+            edges,
+            degree,
+        })
+    }
+
+    // Raise the bound of the node by subgradient optimization and return its children.
+    // The best tour is updated when a 1-tree is a tour.
+    fn branch(&mut self, mut node: Node, iterations: usize, best: &mut Solution) -> Vec<Node> {
+        if !self.restore(&node.constraints) {
+            return vec![];
+        }
+
+        let mut tree: Option<OneTree> = None;
+        let mut penalties = node.penalties.clone();
+        // Step size factor halved when the bound does not rise for a while
+        let mut factor = 2.;
+        let mut since_rise = 0;
+        for _ in 0..iterations.max(1) {
+            let Some(current) = self.one_tree(&penalties) else {
+                return vec![];
+            };
+
+            if tree.as_ref().is_none_or(|t| current.bound > t.bound) {
+                node.penalties.copy_from_slice(&penalties);
+                tree = Some(current.clone());
+                since_rise = 0;
+            } else {
+                since_rise += 1;
+                if since_rise >= self.city_len / 2 {
+                    factor /= 2.;
+                    since_rise = 0;
+                }
+            }
+
+            // A 1-tree which is a tour is the shortest tour of the node
+            if current.degree.iter().all(|d| *d == 2) {
+                let cost = current
+                    .edges
+                    .iter()
+                    .map(|(i, j)| self.matrix.get(*i, *j))
+                    .sum::<Cost>();
+                if cost < best.cost {
+                    best.cities = tour_of(&current.edges, self.city_len);
+                    best.cost = cost;
+                }
+                return vec![];
+            }
+
+            if round_up(current.bound) >= best.cost {
+                return vec![];
+            }
+
+            let norm = current
+                .degree
+                .iter()
+                .map(|d| (*d as f64 - 2.).powi(2))
+                .sum::<f64>();
+            let step = factor * (best.cost as f64 - current.bound) / norm;
+            for (penalty, d) in penalties.iter_mut().zip(current.degree.iter()) {
+                *penalty += step * (*d as f64 - 2.);
+            }
+        }
+
+        let tree = tree.unwrap();
+        node.bound = node.bound.max(tree.bound);
+        if round_up(node.bound) >= best.cost {
+            return vec![];
+        }
+
+        // Branch on the city with the most edges, ignoring city 0 whose degree is always 2
+        let city = (1..self.city_len).max_by_key(|i| tree.degree[*i]).unwrap();
+        let mut free = tree
+            .edges
+            .iter()
+            .filter(|(i, j)| *i == city || *j == city)
+            .filter(|(i, j)| self.get(*i, *j) == EdgeState::Free)
+            .map(|(i, j)| (*i, *j))
+            .collect::<Vec<(usize, usize)>>();
+        free.sort_by_key(|(i, j)| std::cmp::Reverse(self.matrix.get(*i, *j)));
+        let included = (0..self.city_len)
+            .filter(|j| self.get(city, *j) == EdgeState::Included)
+            .count();
+
+        // Children exclude the first edge, include it and exclude the second one, and
+        // include both unless the city already has an included edge
+        let (e1, e2) = (free[0], free[1]);
+        let child = |extra: &[(usize, usize, EdgeState)]| {
+            let mut constraints = node.constraints.clone();
+            constraints.extend_from_slice(extra);
+            Node {
+                bound: node.bound,
+                penalties: node.penalties.clone(),
+                constraints,
+            }
+        };
+        let mut children = vec![
+            child(&[(e1.0, e1.1, EdgeState::Excluded)]),
+            child(&[
+                (e1.0, e1.1, EdgeState::Included),
+                (e2.0, e2.1, EdgeState::Excluded),
+            ]),
+        ];
+        if included == 0 {
+            children.push(child(&[
+                (e1.0, e1.1, EdgeState::Included),
+                (e2.0, e2.1, EdgeState::Included),
+            ]));
+        }
+        children
+    }
+}
+
+// Order of cities of edges making a single cycle
+fn tour_of(edges: &[(usize, usize)], city_len: usize) -> Vec<usize> {
+    let mut adj = vec![vec![]; city_len];
+    for (i, j) in edges.iter() {
+        adj[*i].push(*j);
+        adj[*j].push(*i);
+    }
+
+    let mut cities_idx = vec![0];
+    let (mut prev, mut city) = (0, adj[0][0]);
+    while city != 0 {
+        cities_idx.push(city);
+        let next = if adj[city][0] == prev {
+            adj[city][1]
+        } else {
+            adj[city][0]
+        };
+        (prev, city) = (city, next);
+    }
+    cities_idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100},
+        held_karp,
+        metric::{CoordMetric, EdgeWeightType},
+        tsplib::Instance,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn assert_tour(solution: &Solution, city_len: usize) {
+        let mut sorted = solution.cities.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..city_len).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn optimal() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for city_len in 1..=14 {
+            let coords = (0..city_len)
+                .map(|_| (rng.gen_range(0. ..1000.), rng.gen_range(0. ..1000.)))
+                .collect();
+            let metric = CoordMetric::new(coords, EdgeWeightType::Euc2d);
+            let optimum = held_karp::solver(&metric).cost;
+
+            // A poor upper bound makes the search branch
+            for initial in [None, Some((0..city_len).collect())] {
+                let mut bb = BranchAndBound::new();
+                if let Some(cities_idx) = initial {
+                    bb = bb.initial_tour(cities_idx);
+                }
+                let result = bb.solve(&metric);
+                assert_tour(&result.solution, city_len);
+                assert_eq!(
+                    result.solution.cost,
+                    metric.tour_length(&result.solution.cities)
+                );
+                assert_eq!(result.solution.cost, optimum, "{} cities", city_len);
+                assert!(result.is_optimal());
+                assert_eq!(result.gap(), 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn berlin52() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let result = BranchAndBound::new().solve(&instance);
+        assert_tour(&result.solution, instance.len());
+        assert_eq!(result.solution.cost, 7542);
+        assert_eq!(result.lower_bound, 7542);
+        assert!(result.is_optimal());
+    }
+
+    #[test]
+    fn limits() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let identity = (0..instance.len()).collect::<Vec<usize>>();
+
+        // Search stopping early keeps the bound below the optimum and reports the gap
+        let result = BranchAndBound::new()
+            .initial_tour(identity.clone())
+            .node_limit(3)
+            .solve(&instance);
+        assert_tour(&result.solution, instance.len());
+        assert_eq!(result.nodes, 3);
+        assert!(result.lower_bound <= 20749);
+        assert!(result.solution.cost >= 20749);
+        assert!(!result.is_optimal());
+        assert!(result.gap() > 0.);
+        assert_eq!(
+            result.gap(),
+            (result.solution.cost - result.lower_bound) as f64 / result.lower_bound as f64
+        );
+
+        // The given tour is kept without time to search
+        let result = BranchAndBound::new()
+            .initial_tour(identity.clone())
+            .time_limit(Duration::ZERO)
+            .solve(&instance);
+        assert_eq!(result.nodes, 0);
+        assert_eq!(result.solution.cities, identity);
+        assert!(result.lower_bound > 0);
+        assert!(result.lower_bound <= 20749);
+        assert!(result.gap().is_finite());
+
+        // A zero node limit also reports a bound of the unprocessed root
+        let result = BranchAndBound::new().node_limit(0).solve(&instance);
+        assert_eq!(result.nodes, 0);
+        assert!(result.lower_bound > 0);
+        assert!(result.lower_bound <= 20749);
+    }
+
+    #[test]
+    fn constructor() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let mut events = vec![];
+        let solution =
+            Pipeline::new(BranchAndBound::new()).solve_observed(&instance, &mut |event: &Event| {
+                if let Event::LowerBound { lower_bound, cost } = event {
+                    events.push((*lower_bound, *cost));
+                }
+            });
+        assert_eq!(solution.cost, 7542);
+        assert_eq!(events, vec![(7542, 7542)]);
+    }
+
+    // Asymmetric metrics get the heuristic tour without searching 1-trees
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let result = BranchAndBound::new().solve(&metric);
+        assert_tour(&result.solution, metric.len());
+        assert_eq!(result.nodes, 0);
+        assert!(result.lower_bound <= result.solution.cost);
+        let heuristic = result.solution.cities;
+
+        // The bound holds for the optimum of asymmetric distances
+        let small = random_asymmetric(12, 0);
+        let result = BranchAndBound::new().solve(&small);
+        assert!(result.lower_bound <= held_karp::solver(&small).cost);
+        assert!(result.lower_bound > 0);
+
+        let mut events = 0;
+        let solution =
+            Pipeline::new(BranchAndBound::new()).solve_observed(&metric, &mut |event: &Event| {
+                if let Event::LowerBound { .. } = event {
+                    events += 1;
+                }
+            });
+        assert_eq!(solution.cities, heuristic);
+        assert_eq!(events, 0);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::greedy;
+use crate::matrix::DistanceMatrix;
+use crate::metric::{Cost, Metric};
+use crate::observer::{Event, Observer};
+use crate::solver::ConstructionHeuristic;
+use crate::unionfind::UnionFind;
+
+// Largest number of odd degree cities matched exactly. The table of the matching takes
+// 2^n costs, which is 4 MB for 20 cities.
+pub const EXACT_MATCHING: usize = 20;
+
+// Christofides' algorithm joining a minimum spanning tree and a minimum perfect matching
+// of its odd degree cities into an Eulerian multigraph, whose Euler tour is shortcut
+// by skipping visited cities. On metric instances the tour is at most 1.5 times the
+// optimum because the tree and the matching are at most 1 and 0.5 times of it.
+// The matching is exact with up to EXACT_MATCHING odd cities. With more of them it is
+// greedy improved by exchanging partners of two pairs, and the 1.5 bound is not
+// guaranteed although tours are as good in practice.
+pub fn solver<M: Metric + ?Sized>(observer: &mut dyn Observer, metric: &M) -> Vec<usize> {
+    let city_len = metric.len();
+    if city_len <= 3 {
+        return (0..city_len).collect();
+    }
+    let matrix = DistanceMatrix::from_metric(metric);
+
+    let mut edges = spanning_tree(metric);
+    let mut degree = vec![0; city_len];
+    for (i, j) in edges.iter() {
+        degree[*i] += 1;
+        degree[*j] += 1;
+        observer.notify(&Event::EdgeAdded(*i, *j));
+    }
+
+    // Handshaking lemma makes the number of odd cities even
+    let odd = (0..city_len)
+        .filter(|i| degree[*i] % 2 == 1)
+        .collect::<Vec<usize>>();
+    let matching = if odd.len() <= EXACT_MATCHING {
+        exact_matching(&matrix, &odd)
+    } else {
+        greedy_matching(&matrix, &odd)
+    };
+    for (i, j) in matching.iter() {
+        observer.notify(&Event::EdgeAdded(*i, *j));
+    }
+    edges.extend(matching);
+
+    // Shortcut the Euler tour
+    let mut visited = vec![false; city_len];
+    euler_tour(city_len, &edges)
+        .into_iter()
+        .filter(|city| !std::mem::replace(&mut visited[*city], true))
+        .collect()
+}
+
+// 1.5-approximation for metric instances
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Christofides;
+
+impl ConstructionHeuristic for Christofides {
+    fn construct(&mut self, metric: &dyn Metric, observer: &mut dyn Observer) -> Vec<usize> {
+        solver(observer, metric)
+    }
+}
+
+// Edges of the minimum spanning tree by Kruskal's algorithm
+fn spanning_tree<M: Metric + ?Sized>(metric: &M) -> Vec<(usize, usize)> {
+    let city_len = metric.len();
+    let mut uf = UnionFind::new(city_len);
+    let mut tree = vec![];
+    for (_, i, j) in greedy::edges(metric) {
+        if tree.len() == city_len - 1 {
+            break;
+        }
+        if !uf.same(i, j) {
+            uf.unite(i, j);
+            tree.push((i, j));
+        }
+    }
+    tree
+}
+
+// Minimum perfect matching by dynamic programming over sets of matched cities.
+// The first unmatched city is always matched next, so it takes O(2^n * n) time.
+fn exact_matching(matrix: &DistanceMatrix, cities: &[usize]) -> Vec<(usize, usize)> {
+    let len = cities.len();
+    let full = (1 << len) - 1;
+    let dist = |a: usize, b: usize| matrix.get(cities[a], cities[b]);
+
+    // Shortest matching of the cities not in set, Cost::MAX if there is none
+    let mut table = vec![Cost::MAX; 1 << len];
+    table[full] = 0;
+    let first = |set: usize| (!set).trailing_zeros() as usize;
+    for set in (0..full).rev() {
+        let a = first(set);
+        for b in a + 1..len {
+            let rest = table[set | 1 << a | 1 << b];
+            if set & (1 << b) == 0 && rest != Cost::MAX {
+                table[set] = table[set].min(rest + dist(a, b));
+            }
+        }
+    }
+
+    // Follow pairs giving the shortest matching
+    let mut matching = vec![];
+    let mut set = 0;
+    while set != full {
+        let a = first(set);
+        let b = (a + 1..len)
+            .filter(|b| set & (1 << b) == 0)
+            .find(|b| {
+                let rest = table[set | 1 << a | 1 << b];
+                rest != Cost::MAX && rest + dist(a, *b) == table[set]
+            })
+            .unwrap();
+        matching.push((cities[a], cities[b]));
+        set |= 1 << a | 1 << b;
+    }
+    matching
+}
+
+// Match the closest pairs first, then exchange partners of two pairs (a, b) and (c, d)
+// into (a, c) and (b, d) or (a, d) and (b, c) while the matching gets shorter
+fn greedy_matching(matrix: &DistanceMatrix, cities: &[usize]) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    for (x, a) in cities.iter().enumerate() {
+        for b in cities[x + 1..].iter() {
+            pairs.push((matrix.get(*a, *b), *a, *b));
+        }
+    }
+    pairs.sort();
+
+    let mut matched = vec![false; matrix.dimension()];
+    let mut matching = vec![];
+    for (_, a, b) in pairs {
+        if !matched[a] && !matched[b] {
+            matched[a] = true;
+            matched[b] = true;
+            matching.push((a, b));
+        }
+    }
+
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for x in 0..matching.len() {
+            for y in x + 1..matching.len() {
+                let ((a, b), (c, d)) = (matching[x], matching[y]);
+                let current = matrix.get(a, b) + matrix.get(c, d);
+                let crossed = matrix.get(a, c) + matrix.get(b, d);
+                let other = matrix.get(a, d) + matrix.get(b, c);
+                if crossed < current && crossed <= other {
+                    (matching[x], matching[y]) = ((a, c), (b, d));
+                    improved = true;
+                } else if other < current {
+                    (matching[x], matching[y]) = ((a, d), (b, c));
+                    improved = true;
+                }
+            }
+        }
+    }
+    matching
+}
+
+// Cities of an Euler tour of a connected multigraph whose cities have even degrees
+// by Hierholzer's algorithm. The first city is repeated at the end.
+fn euler_tour(city_len: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut adj = vec![vec![]; city_len];
+    for (e, (i, j)) in edges.iter().enumerate() {
+        adj[*i].push((*j, e));
+        adj[*j].push((*i, e));
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut stack = vec![0];
+    let mut tour = vec![];
+    while let Some(&city) = stack.last() {
+        // Drop edges already walked from the other end
+        while adj[city].last().is_some_and(|(_, e)| used[*e]) {
+            adj[city].pop();
+        }
+        match adj[city].pop() {
+            Some((next, e)) => {
+                used[e] = true;
+                stack.push(next);
+            }
+            None => tour.push(stack.pop().unwrap()),
+        }
+    }
+    tour
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bench_tsp, bounds,
+        common::{TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225},
+        held_karp,
+        metric::{CoordMetric, EdgeWeightType},
+        observer::NullObserver,
+        test_tsp,
+        tsplib::Instance,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use test::Bencher;
+
+    fn random_matrix(city_len: usize, rng: &mut StdRng) -> DistanceMatrix {
+        let coords = (0..city_len)
+            .map(|_| (rng.gen_range(0. ..1000.), rng.gen_range(0. ..1000.)))
+            .collect();
+        DistanceMatrix::from_metric(&CoordMetric::new(coords, EdgeWeightType::Euc2d))
+    }
+
+    fn matching_cost(matrix: &DistanceMatrix, matching: &[(usize, usize)]) -> Cost {
+        matching.iter().map(|(a, b)| matrix.get(*a, *b)).sum()
+    }
+
+    fn assert_tour(cities_idx: &[usize], city_len: usize) {
+        let mut sorted = cities_idx.to_vec();
+        sorted.sort();
+        assert_eq!(sorted, (0..city_len).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn matching() {
+        // Shortest perfect matching by trying all pairings
+        fn brute_force(matrix: &DistanceMatrix, cities: &[usize]) -> Cost {
+            if cities.is_empty() {
+                return 0;
+            }
+            (1..cities.len())
+                .map(|b| {
+                    let mut rest = cities[1..].to_vec();
+                    rest.remove(b - 1);
+                    matrix.get(cities[0], cities[b]) + brute_force(matrix, &rest)
+                })
+                .min()
+                .unwrap()
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for len in (0..=10).step_by(2) {
+            let matrix = random_matrix(len + 3, &mut rng);
+            let cities = (3..len + 3).collect::<Vec<usize>>();
+
+            let exact = exact_matching(&matrix, &cities);
+            let greedy = greedy_matching(&matrix, &cities);
+            for matching in [&exact, &greedy] {
+                let mut matched = matching
+                    .iter()
+                    .flat_map(|(a, b)| [*a, *b])
+                    .collect::<Vec<usize>>();
+                matched.sort();
+                assert_eq!(matched, cities);
+            }
+
+            let optimum = brute_force(&matrix, &cities);
+            assert_eq!(matching_cost(&matrix, &exact), optimum);
+            assert!(matching_cost(&matrix, &greedy) >= optimum);
+        }
+    }
+
+    #[test]
+    fn euler() {
+        // Two triangles sharing city 0
+        let edges = [(0, 1), (1, 2), (2, 0), (0, 3), (3, 4), (4, 0)];
+        let tour = euler_tour(5, &edges);
+        assert_eq!(tour.len(), edges.len() + 1);
+        assert_eq!(tour.first(), tour.last());
+        let mut walked = tour
+            .windows(2)
+            .map(|w| (w[0].min(w[1]), w[0].max(w[1])))
+            .collect::<Vec<(usize, usize)>>();
+        walked.sort();
+        let mut expected = edges.map(|(i, j)| (i.min(j), i.max(j))).to_vec();
+        expected.sort();
+        assert_eq!(walked, expected);
+
+        // Parallel edges from the tree and the matching
+        assert_eq!(euler_tour(2, &[(0, 1), (0, 1)]).len(), 3);
+    }
+
+    #[test]
+    fn approximation() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for city_len in 1..=14 {
+            let matrix = random_matrix(city_len, &mut rng);
+            let cities_idx = solver(&mut NullObserver, &matrix);
+            assert_tour(&cities_idx, city_len);
+
+            let optimum = held_karp::solver(&matrix).cost;
+            let cost = matrix.tour_length(&cities_idx);
+            assert!(2 * cost <= 3 * optimum, "{} cities", city_len);
+        }
+
+        // Tours are within 1.5 times the lower bound of the optimum
+        for file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225] {
+            let instance = Instance::load(file).unwrap();
+            let mut edges = 0;
+            let cities_idx = Christofides.construct(&instance, &mut |event: &Event| {
+                if let Event::EdgeAdded(..) = event {
+                    edges += 1;
+                }
+            });
+            assert_tour(&cities_idx, instance.len());
+            // The tree and half of the odd cities
+            assert!(edges > instance.len() - 1);
+
+            let cost = instance.tour_length(&cities_idx);
+            let lower_bound = bounds::held_karp(&instance, cost, bounds::ITERATIONS);
+            assert!(2 * cost <= 3 * lower_bound, "{}", file);
+        }
+    }
+
+    #[test]
+    fn all() {
+        test_tsp!(solver, "christofides", false, TSP_FILE_BERLIN52);
+        test_tsp!(solver, "christofides", false, TSP_FILE_KROC100);
+        test_tsp!(solver, "christofides", false, TSP_FILE_TS225);
+    }
+
+    #[bench]
+    fn bench_berlin(b: &mut Bencher) {
+        bench_tsp!(b, solver, TSP_FILE_BERLIN52);
+    }
+
+    #[bench]
+    fn bench_kroc(b: &mut Bencher) {
+        bench_tsp!(b, solver, TSP_FILE_KROC100);
+    }
+
+    #[bench]
+    fn bench_ts(b: &mut Bencher) {
+        bench_tsp!(b, solver, TSP_FILE_TS225);
+    }
+}
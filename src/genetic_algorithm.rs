@@ -0,0 +1,988 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::greedy;
+use crate::iterated_local_search::double_bridge;
+use crate::metric::{Cost, Metric};
+use crate::nearest_neighbor;
+use crate::neighbors::NeighborLists;
+use crate::observer::{Event, NullObserver, Observer};
+use crate::solver::LocalSearch;
+use crate::two_opt::{self, NEIGHBORS};
+use std::time::Instant;
+
+// Number of tours in the population by default
+pub const POPULATION: usize = 30;
+// Number of generations by default
+pub const GENERATIONS: usize = 100;
+// Probability that a child is mutated by default
+pub const MUTATION: f64 = 0.1;
+// Number of best tours copied to the next generation by default
+pub const ELITES: usize = 2;
+// Number of tours competing for each parent
+const TOURNAMENT: usize = 3;
+// Number of AB-cycles tried by Edge Assembly Crossover for each child
+const AB_CYCLES: usize = 10;
+
+// How a child tour is made from two parents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Crossover {
+    // Order crossover (OX). A part of the first parent is kept at the same positions
+    // and the other cities follow in the order of the second parent.
+    Order,
+    // Partially mapped crossover (PMX). A part of the first parent is kept at the same
+    // positions and the other cities stay at their positions in the second parent
+    // unless the kept part moves them.
+    PartiallyMapped,
+    // Edge recombination (ERX). The tour is built from edges of either parent, choosing
+    // the next city with the fewest edges left.
+    EdgeRecombination,
+    // Edge Assembly Crossover (EAX). Edges of the first parent are exchanged with edges of
+    // the second one along an AB-cycle, which alternates edges of the two parents, and the
+    // subtours it makes are merged with the shortest 2-opt like reconnections.
+    #[default]
+    EdgeAssembly,
+}
+
+// Genetic algorithm evolving a population of tours. The population starts with the given
+// tour, greedy and nearest neighbor tours and nearest neighbor tours from random cities
+// choosing among the nearest cities at random. Each generation copies the elites and
+// fills the rest with children of parents chosen by tournaments. Children are mutated
+// by double-bridge kicks and improved by 2-opt when memetic is set. To keep the population
+// diverse, children equal to a tour already in the next generation are dropped, and new
+// random tours fill the generation if too many children are dropped.
+// The shortest tour found is returned.
+#[derive(Debug, Clone)]
+pub struct GeneticAlgorithm {
+    crossover: Crossover,
+    population: usize,
+    generations: usize,
+    mutation: f64,
+    elites: usize,
+    memetic: bool,
+    neighbors: usize,
+}
+
+impl Default for GeneticAlgorithm {
+    fn default() -> GeneticAlgorithm {
+        GeneticAlgorithm {
+            crossover: Crossover::default(),
+            population: POPULATION,
+            generations: GENERATIONS,
+            mutation: MUTATION,
+            elites: ELITES,
+            memetic: true,
+            neighbors: NEIGHBORS,
+        }
+    }
+}
+
+impl GeneticAlgorithm {
+    pub fn new() -> GeneticAlgorithm {
+        GeneticAlgorithm::default()
+    }
+
+    pub fn crossover(mut self, crossover: Crossover) -> GeneticAlgorithm {
+        self.crossover = crossover;
+        self
+    }
+
+    // Number of tours in the population, which is at least two
+    pub fn population(mut self, population: usize) -> GeneticAlgorithm {
+        self.population = population.max(2);
+        self
+    }
+
+    // Number of generations. The deadline of the pipeline also stops the search.
+    pub fn generations(mut self, generations: usize) -> GeneticAlgorithm {
+        self.generations = generations;
+        self
+    }
+
+    pub fn mutation(mut self, probability: f64) -> GeneticAlgorithm {
+        self.mutation = probability;
+        self
+    }
+
+    pub fn elites(mut self, elites: usize) -> GeneticAlgorithm {
+        self.elites = elites;
+        self
+    }
+
+    // Whether all tours are improved by 2-opt
+    pub fn memetic(mut self, memetic: bool) -> GeneticAlgorithm {
+        self.memetic = memetic;
+        self
+    }
+
+    // Number of nearest neighbors of each city used by 2-opt, random tours and EAX
+    pub fn neighbors(mut self, k: usize) -> GeneticAlgorithm {
+        self.neighbors = k;
+        self
+    }
+
+    // Evolve the population from cities_idx and leave the shortest tour found in it.
+    // Returns the number of generations.
+    pub fn evolve<M: Metric + ?Sized, R: Rng>(
+        &self,
+        observer: &mut dyn Observer,
+        metric: &M,
+        cities_idx: &mut [usize],
+        rng: &mut R,
+        deadline: Option<Instant>,
+    ) -> usize {
+        let city_len = cities_idx.len();
+        let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        // Double-bridge needs eight cities
+        if city_len < 8 {
+            return 0;
+        }
+
+        let neighbors = NeighborLists::new(metric, self.neighbors);
+        let refine = |cities_idx: &mut [usize]| {
+            if self.memetic {
+                two_opt::local_optimum(
+                    &mut NullObserver,
+                    metric,
+                    cities_idx,
+                    &neighbors,
+                    false,
+                    deadline,
+                );
+            }
+        };
+
+        let mut population = Population::default();
+        let seeds = [
+            cities_idx.to_vec(),
+            greedy::solver(&mut NullObserver, metric),
+            nearest_neighbor::solver(&mut NullObserver, metric),
+        ];
+        for mut tour in seeds {
+            refine(&mut tour);
+            population.insert(metric, tour);
+        }
+        // Random tours can be equal to others for tiny instances
+        for _ in 0..self.population * 4 {
+            if population.len() >= self.population || expired() {
+                break;
+            }
+            let mut tour = random_nearest_neighbor(metric, &neighbors, rng);
+            refine(&mut tour);
+            population.insert(metric, tour);
+        }
+        population.sort();
+
+        let mut best_cost = metric.tour_length(cities_idx);
+        let mut keep_best = |population: &Population, cities_idx: &mut [usize]| {
+            let best = &population.individuals[0];
+            if best.cost < best_cost {
+                best_cost = best.cost;
+                cities_idx.copy_from_slice(&best.cities);
+                observer.notify(&Event::NewBest {
+                    cities_idx,
+                    cost: best_cost,
+                });
+            }
+        };
+        keep_best(&population, cities_idx);
+
+        let mut generations = 0;
+        while generations < self.generations && !expired() {
+            generations += 1;
+
+            let mut next = Population::default();
+            for individual in population.individuals.iter().take(self.elites) {
+                next.insert(metric, individual.cities.clone());
+            }
+
+            let mut attempts = 0;
+            while next.len() < self.population && attempts < self.population * 4 && !expired() {
+                attempts += 1;
+
+                let a = population.tournament(rng);
+                let b = population.tournament(rng);
+                let mut child = if a == b {
+                    a.to_vec()
+                } else {
+                    self.crossover.cross(metric, a, b, &neighbors, rng)
+                };
+                if rng.gen_bool(self.mutation) {
+                    double_bridge(&mut child, rng);
+                }
+                refine(&mut child);
+                next.insert(metric, child);
+            }
+
+            // Random tours take the place of dropped children. Small instances may not
+            // have enough different tours.
+            for _ in 0..self.population * 4 {
+                if next.len() >= self.population || expired() {
+                    break;
+                }
+                let mut tour = random_nearest_neighbor(metric, &neighbors, rng);
+                refine(&mut tour);
+                next.insert(metric, tour);
+            }
+
+            // Tours of the last generation fill the rest if the deadline has passed
+            for individual in population.individuals.drain(..) {
+                if next.len() >= self.population {
+                    break;
+                }
+                next.insert(metric, individual.cities);
+            }
+
+            population = next;
+            population.sort();
+            keep_best(&population, cities_idx);
+        }
+
+        generations
+    }
+}
+
+impl LocalSearch for GeneticAlgorithm {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        rng: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        self.evolve(observer, metric, cities_idx, rng, deadline);
+    }
+}
+
+impl Crossover {
+    // Child of parents a and b
+    pub fn cross<M: Metric + ?Sized, R: Rng>(
+        &self,
+        metric: &M,
+        a: &[usize],
+        b: &[usize],
+        neighbors: &NeighborLists,
+        rng: &mut R,
+    ) -> Vec<usize> {
+        match self {
+            Crossover::Order => order(a, b, rng),
+            Crossover::PartiallyMapped => partially_mapped(a, b, rng),
+            Crossover::EdgeRecombination => edge_recombination(a, b, rng),
+            Crossover::EdgeAssembly => edge_assembly(metric, a, b, neighbors, rng),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Individual {
+    cities: Vec<usize>,
+    cost: Cost,
+    // Tour starting from city 0 in the direction of its smaller neighbor,
+    // which is the same for all rotations and reflections of the tour
+    canonical: Vec<usize>,
+}
+
+// Tours without duplicates, sorted by length after sort
+#[derive(Debug, Clone, Default)]
+struct Population {
+    individuals: Vec<Individual>,
+}
+
+impl Population {
+    fn len(&self) -> usize {
+        self.individuals.len()
+    }
+
+    // Add the tour unless it is already in the population
+    fn insert<M: Metric + ?Sized>(&mut self, metric: &M, cities: Vec<usize>) -> bool {
+        let cost = metric.tour_length(&cities);
+        let canonical = canonical(&cities);
+        if self
+            .individuals
+            .iter()
+            .any(|individual| individual.cost == cost && individual.canonical == canonical)
+        {
+            return false;
+        }
+
+        self.individuals.push(Individual {
+            cities,
+            cost,
+            canonical,
+        });
+        true
+    }
+
+    fn sort(&mut self) {
+        self.individuals.sort_by_key(|individual| individual.cost);
+    }
+
+    // Shortest of TOURNAMENT tours chosen at random from the sorted population
+    fn tournament<R: Rng>(&self, rng: &mut R) -> &[usize] {
+        let i = (0..TOURNAMENT)
+            .map(|_| rng.gen_range(0..self.len()))
+            .min()
+            .unwrap();
+        &self.individuals[i].cities
+    }
+}
+
+fn canonical(cities_idx: &[usize]) -> Vec<usize> {
+    let city_len = cities_idx.len();
+    let start = cities_idx.iter().position(|city| *city == 0).unwrap();
+
+    let mut canonical = cities_idx.to_vec();
+    canonical.rotate_left(start);
+    if city_len > 2 && canonical[1] > canonical[city_len - 1] {
+        canonical[1..].reverse();
+    }
+    canonical
+}
+
+// Nearest neighbor tour from a random city which goes to one of the two nearest cities
+// not visited yet at random
+fn random_nearest_neighbor<M: Metric + ?Sized, R: Rng>(
+    metric: &M,
+    neighbors: &NeighborLists,
+    rng: &mut R,
+) -> Vec<usize> {
+    let city_len = metric.len();
+    let mut visited = vec![false; city_len];
+
+    let mut city = rng.gen_range(0..city_len);
+    visited[city] = true;
+    let mut cities_idx = vec![city];
+
+    while cities_idx.len() < city_len {
+        let candidates = neighbors
+            .get(city)
+            .iter()
+            .copied()
+            .filter(|c| !visited[*c])
+            .take(2)
+            .collect::<Vec<usize>>();
+        city = match candidates.choose(rng) {
+            Some(c) => *c,
+            // All neighbors are visited
+            None => (0..city_len)
+                .filter(|c| !visited[*c])
+                .min_by_key(|c| metric.dist(city, *c))
+                .unwrap(),
+        };
+        visited[city] = true;
+        cities_idx.push(city);
+    }
+
+    cities_idx
+}
+
+// Two cut points 0 <= i < j <= len
+fn cut_points<R: Rng>(city_len: usize, rng: &mut R) -> (usize, usize) {
+    let i = rng.gen_range(0..city_len);
+    let j = rng.gen_range(i + 1..=city_len);
+    (i, j)
+}
+
+fn order<R: Rng>(a: &[usize], b: &[usize], rng: &mut R) -> Vec<usize> {
+    let city_len = a.len();
+    let (i, j) = cut_points(city_len, rng);
+
+    let mut child = vec![0; city_len];
+    let mut kept = vec![false; city_len];
+    child[i..j].copy_from_slice(&a[i..j]);
+    for city in a[i..j].iter() {
+        kept[*city] = true;
+    }
+
+    // The rest is filled from j in the order of b starting from j
+    let mut k = j % city_len;
+    for step in 0..city_len {
+        let city = b[(j + step) % city_len];
+        if !kept[city] {
+            child[k] = city;
+            k = (k + 1) % city_len;
+        }
+    }
+    child
+}
+
+fn partially_mapped<R: Rng>(a: &[usize], b: &[usize], rng: &mut R) -> Vec<usize> {
+    let city_len = a.len();
+    let (i, j) = cut_points(city_len, rng);
+
+    // Swapping each city of the part into its position moves the city at that position
+    // to where the mapping sends it
+    let mut child = b.to_vec();
+    let mut pos = vec![0; city_len];
+    for (k, city) in child.iter().enumerate() {
+        pos[*city] = k;
+    }
+    for k in i..j {
+        let other = pos[a[k]];
+        child.swap(k, other);
+        pos[child[k]] = k;
+        pos[child[other]] = other;
+    }
+    child
+}
+
+fn edge_recombination<R: Rng>(a: &[usize], b: &[usize], rng: &mut R) -> Vec<usize> {
+    let city_len = a.len();
+
+    // Neighbors of each city in either parent
+    let mut edges = vec![Vec::with_capacity(4); city_len];
+    for parent in [a, b] {
+        for k in 0..city_len {
+            let (x, y) = (parent[k], parent[(k + 1) % city_len]);
+            if !edges[x].contains(&y) {
+                edges[x].push(y);
+                edges[y].push(x);
+            }
+        }
+    }
+
+    let mut visited = vec![false; city_len];
+    // Cities not visited yet and the position of each city in it
+    let mut rest = (0..city_len).collect::<Vec<usize>>();
+    let mut rest_pos = (0..city_len).collect::<Vec<usize>>();
+    let mut visit = |city: usize, visited: &mut [bool], rest: &mut Vec<usize>| {
+        visited[city] = true;
+        let k = rest_pos[city];
+        rest.swap_remove(k);
+        if k < rest.len() {
+            rest_pos[rest[k]] = k;
+        }
+    };
+
+    let mut city = a[0];
+    visit(city, &mut visited, &mut rest);
+    let mut child = vec![city];
+
+    while child.len() < city_len {
+        let left = |c: usize| edges[c].iter().filter(|d| !visited[**d]).count();
+        let candidates = edges[city]
+            .iter()
+            .copied()
+            .filter(|c| !visited[*c])
+            .collect::<Vec<usize>>();
+
+        city = match candidates.iter().map(|c| left(*c)).min() {
+            Some(fewest) => {
+                let ties = candidates
+                    .into_iter()
+                    .filter(|c| left(*c) == fewest)
+                    .collect::<Vec<usize>>();
+                *ties.choose(rng).unwrap()
+            }
+            // Dead end
+            None => *rest.choose(rng).unwrap(),
+        };
+        visit(city, &mut visited, &mut rest);
+        child.push(city);
+    }
+    child
+}
+
+// Two cities next to each city in a tour
+fn adjacency(cities_idx: &[usize]) -> Vec<[usize; 2]> {
+    let city_len = cities_idx.len();
+    let mut adj = vec![[0; 2]; city_len];
+    for k in 0..city_len {
+        let city = cities_idx[k];
+        adj[city] = [
+            cities_idx[(k + city_len - 1) % city_len],
+            cities_idx[(k + 1) % city_len],
+        ];
+    }
+    adj
+}
+
+// Tour of a connected adjacency starting from city 0
+fn from_adjacency(adj: &[[usize; 2]]) -> Vec<usize> {
+    let mut cities_idx = Vec::with_capacity(adj.len());
+    let (mut prev, mut city) = (adj[0][0], 0);
+    for _ in 0..adj.len() {
+        cities_idx.push(city);
+        let next = if adj[city][0] == prev {
+            adj[city][1]
+        } else {
+            adj[city][0]
+        };
+        (prev, city) = (city, next);
+    }
+    cities_idx
+}
+
+fn replace(adj: &mut [[usize; 2]], city: usize, old: usize, new: usize) {
+    if adj[city][0] == old {
+        adj[city][0] = new;
+    } else {
+        adj[city][1] = new;
+    }
+}
+
+fn edge_assembly<M: Metric + ?Sized, R: Rng>(
+    metric: &M,
+    a: &[usize],
+    b: &[usize],
+    neighbors: &NeighborLists,
+    rng: &mut R,
+) -> Vec<usize> {
+    let adj_a = adjacency(a);
+    let adj_b = adjacency(b);
+
+    let mut cycles = ab_cycles(&adj_a, &adj_b, rng);
+    if cycles.is_empty() {
+        // Same tours
+        return a.to_vec();
+    }
+    cycles.shuffle(rng);
+
+    let mut best: Option<(Cost, Vec<[usize; 2]>)> = None;
+    for cycle in cycles.iter().take(AB_CYCLES) {
+        let mut adj = adj_a.clone();
+        let mut delta = 0;
+
+        // Cycle edges from even positions are in a and the others are in b.
+        // All edges of a are removed before edges of b fill their places.
+        let edge = |k: usize| (cycle[k], cycle[(k + 1) % cycle.len()]);
+        for k in (0..cycle.len()).step_by(2) {
+            let (x, y) = edge(k);
+            replace(&mut adj, x, y, usize::MAX);
+            replace(&mut adj, y, x, usize::MAX);
+            delta -= metric.dist(x, y);
+        }
+        for k in (1..cycle.len()).step_by(2) {
+            let (x, y) = edge(k);
+            replace(&mut adj, x, usize::MAX, y);
+            replace(&mut adj, y, usize::MAX, x);
+            delta += metric.dist(x, y);
+        }
+        delta += merge_subtours(metric, &mut adj, neighbors);
+
+        if best.as_ref().is_none_or(|(d, _)| delta < *d) {
+            best = Some((delta, adj));
+        }
+    }
+
+    from_adjacency(&best.unwrap().1)
+}
+
+// Split the edges in only one of tours a and b into AB-cycles, closed walks alternating
+// an edge of a and an edge of b. Each cycle starts with an edge of a.
+fn ab_cycles<R: Rng>(adj_a: &[[usize; 2]], adj_b: &[[usize; 2]], rng: &mut R) -> Vec<Vec<usize>> {
+    let city_len = adj_a.len();
+
+    // Each city has as many edges left in a as in b
+    let mut left_a = vec![Vec::with_capacity(2); city_len];
+    let mut left_b = vec![Vec::with_capacity(2); city_len];
+    for city in 0..city_len {
+        for other in adj_a[city] {
+            if !adj_b[city].contains(&other) {
+                left_a[city].push(other);
+            }
+        }
+        for other in adj_b[city] {
+            if !adj_a[city].contains(&other) {
+                left_b[city].push(other);
+            }
+        }
+    }
+
+    // Take one of the edges left at city at random
+    let take = |left: &mut [Vec<usize>], city: usize, rng: &mut R| {
+        let other = left[city].swap_remove(rng.gen_range(0..left[city].len()));
+        let k = left[other].iter().position(|c| *c == city).unwrap();
+        left[other].swap_remove(k);
+        other
+    };
+
+    let mut starts = (0..city_len).collect::<Vec<usize>>();
+    starts.shuffle(rng);
+
+    let mut cycles = vec![];
+    for start in starts {
+        while !left_a[start].is_empty() {
+            // Edges from even positions of the path are in a and the others are in b
+            let mut path = vec![start];
+            loop {
+                let city = *path.last().unwrap();
+                let x = take(&mut left_a, city, rng);
+                let y = take(&mut left_b, x, rng);
+                path.push(x);
+                path.push(y);
+
+                // The walk closes a cycle when it comes back to a city it left by an edge of a
+                let end = path.len() - 1;
+                if let Some(k) = (0..end).step_by(2).rev().find(|k| path[*k] == y) {
+                    cycles.push(path[k..end].to_vec());
+                    path.truncate(k + 1);
+                    if k == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    cycles
+}
+
+// Merge the subtours of adj into a tour, each time connecting the smallest subtour to
+// another one by the shortest exchange of an edge of each.
+// Returns the change of the length.
+fn merge_subtours<M: Metric + ?Sized>(
+    metric: &M,
+    adj: &mut [[usize; 2]],
+    neighbors: &NeighborLists,
+) -> Cost {
+    let city_len = adj.len();
+
+    // Subtour of each city and cities of each subtour
+    let mut label = vec![usize::MAX; city_len];
+    let mut subtours: Vec<Vec<usize>> = vec![];
+    for start in 0..city_len {
+        if label[start] != usize::MAX {
+            continue;
+        }
+        let mut cities = vec![];
+        let (mut prev, mut city) = (adj[start][0], start);
+        loop {
+            label[city] = subtours.len();
+            cities.push(city);
+            let next = if adj[city][0] == prev {
+                adj[city][1]
+            } else {
+                adj[city][0]
+            };
+            (prev, city) = (city, next);
+            if city == start {
+                break;
+            }
+        }
+        subtours.push(cities);
+    }
+
+    let mut delta = 0;
+    for _ in 1..subtours.len() {
+        let (smallest, _) = subtours
+            .iter()
+            .enumerate()
+            .filter(|(_, cities)| !cities.is_empty())
+            .min_by_key(|(_, cities)| cities.len())
+            .unwrap();
+
+        // Replace (x, y) and (u, v) with (x, u) and (y, v) when swapped is false,
+        // or with (x, v) and (y, u) otherwise
+        let mut best: Option<(Cost, usize, usize, usize, usize)> = None;
+        let try_city = |best: &mut Option<(Cost, usize, usize, usize, usize)>, x, y, u: usize| {
+            for v in adj[u] {
+                let removed = metric.dist(x, y) + metric.dist(u, v);
+                for (p, q) in [(u, v), (v, u)] {
+                    let d = metric.dist(x, p) + metric.dist(y, q) - removed;
+                    if best.is_none_or(|(b, ..)| d < b) {
+                        *best = Some((d, x, y, p, q));
+                    }
+                }
+            }
+        };
+        for x in subtours[smallest].iter().copied() {
+            for y in adj[x] {
+                for u in neighbors.get(x).iter().copied() {
+                    if label[u] != smallest {
+                        try_city(&mut best, x, y, u);
+                    }
+                }
+            }
+        }
+        // No neighbor is in another subtour
+        if best.is_none() {
+            for x in subtours[smallest].iter().copied() {
+                for y in adj[x] {
+                    for (u, l) in label.iter().enumerate() {
+                        if *l != smallest {
+                            try_city(&mut best, x, y, u);
+                        }
+                    }
+                }
+            }
+        }
+
+        let (d, x, y, p, q) = best.unwrap();
+        replace(adj, x, y, p);
+        replace(adj, y, x, q);
+        replace(adj, p, q, x);
+        replace(adj, q, p, y);
+        delta += d;
+
+        let other = label[p];
+        let cities = std::mem::take(&mut subtours[smallest]);
+        for city in cities.iter() {
+            label[*city] = other;
+        }
+        subtours[other].extend(cities);
+    }
+
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100},
+        greedy::Greedy,
+        held_karp,
+        metric::{CoordMetric, EdgeWeightType},
+        solver::{ConstructionHeuristic, Pipeline, Solver},
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    const CROSSOVERS: [Crossover; 4] = [
+        Crossover::Order,
+        Crossover::PartiallyMapped,
+        Crossover::EdgeRecombination,
+        Crossover::EdgeAssembly,
+    ];
+
+    fn edges(cities_idx: &[usize]) -> Vec<(usize, usize)> {
+        let city_len = cities_idx.len();
+        let mut edges = (0..city_len)
+            .map(|k| {
+                let (x, y) = (cities_idx[k], cities_idx[(k + 1) % city_len]);
+                (x.min(y), x.max(y))
+            })
+            .collect::<Vec<(usize, usize)>>();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn crossovers() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for crossover in CROSSOVERS {
+            for _ in 0..100 {
+                let mut a = (0..52).collect::<Vec<usize>>();
+                let mut b = a.clone();
+                a.shuffle(&mut rng);
+                b.shuffle(&mut rng);
+
+                // Children visit every city once
+                let child = crossover.cross(&instance, &a, &b, &neighbors, &mut rng);
+                let mut sorted = child.clone();
+                sorted.sort();
+                assert_eq!(sorted, (0..52).collect::<Vec<usize>>(), "{:?}", crossover);
+
+                // Parents equal up to rotation and reflection give the same tour
+                let mut c = a.clone();
+                c.rotate_left(7);
+                c.reverse();
+                let child = crossover.cross(&instance, &a, &c, &neighbors, &mut rng);
+                match crossover {
+                    Crossover::EdgeRecombination | Crossover::EdgeAssembly => {
+                        assert_eq!(edges(&child), edges(&a), "{:?}", crossover)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Order and PMX keep the part of the first parent between the cut points
+        // which are the first random numbers they take
+        let a = (0..10).collect::<Vec<usize>>();
+        let b = [3, 7, 0, 9, 5, 1, 8, 2, 6, 4];
+        for seed in 0..100 {
+            let (i, j) = cut_points(10, &mut StdRng::seed_from_u64(seed));
+            let kept = |child: &[usize]| child[i..j] == a[i..j];
+
+            let child = order(&a, &b, &mut StdRng::seed_from_u64(seed));
+            assert!(kept(&child));
+            // The rest follows the order of b from the second cut point
+            let rest = (j..j + 10)
+                .map(|k| child[k % 10])
+                .filter(|city| !a[i..j].contains(city))
+                .collect::<Vec<usize>>();
+            let order_b = (j..j + 10)
+                .map(|k| b[k % 10])
+                .filter(|city| !a[i..j].contains(city))
+                .collect::<Vec<usize>>();
+            assert_eq!(rest, order_b);
+
+            // Cities of b outside the part which are not in the part of a stay
+            let child = partially_mapped(&a, &b, &mut StdRng::seed_from_u64(seed));
+            assert!(kept(&child));
+            for k in (0..i).chain(j..10) {
+                if !a[i..j].contains(&b[k]) {
+                    assert_eq!(child[k], b[k]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ab_cycles_alternate() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut a = (0..30).collect::<Vec<usize>>();
+        let mut b = a.clone();
+
+        for _ in 0..100 {
+            a.shuffle(&mut rng);
+            b.shuffle(&mut rng);
+            let (adj_a, adj_b) = (adjacency(&a), adjacency(&b));
+            let only_a = edges(&a)
+                .into_iter()
+                .filter(|e| !edges(&b).contains(e))
+                .count();
+
+            let cycles = ab_cycles(&adj_a, &adj_b, &mut rng);
+            let mut total = 0;
+            for cycle in cycles.iter() {
+                assert!(cycle.len() >= 4 && cycle.len() % 2 == 0);
+                for k in 0..cycle.len() {
+                    let (x, y) = (cycle[k], cycle[(k + 1) % cycle.len()]);
+                    let adj = if k % 2 == 0 { &adj_a } else { &adj_b };
+                    assert!(adj[x].contains(&y));
+                }
+                total += cycle.len();
+            }
+            // Every edge in only one of the tours is in exactly one cycle
+            assert_eq!(total, only_a * 2);
+        }
+    }
+
+    #[test]
+    fn edge_assembly_child() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let a = greedy::solver(&mut NullObserver, &instance);
+        let b = nearest_neighbor::solver(&mut NullObserver, &instance);
+        for _ in 0..20 {
+            let child = edge_assembly(&instance, &a, &b, &neighbors, &mut rng);
+            let mut sorted = child.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..100).collect::<Vec<usize>>());
+
+            // Most edges of the child come from the parents
+            let from_parents = edges(&child)
+                .into_iter()
+                .filter(|e| edges(&a).contains(e) || edges(&b).contains(e))
+                .count();
+            assert!(from_parents >= 90);
+        }
+    }
+
+    // All crossovers get past the 2-opt local optimum
+    #[test]
+    fn evolve() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+
+            for crossover in CROSSOVERS {
+                let solution = Pipeline::new(Greedy)
+                    .then(
+                        GeneticAlgorithm::new()
+                            .crossover(crossover)
+                            .population(20)
+                            .generations(20),
+                    )
+                    .seed(0)
+                    .solve(&instance);
+
+                println!(
+                    "{} {:?}: 2-opt {} ga {}",
+                    tsp_file, crossover, two_opt.cost, solution.cost
+                );
+                assert!(solution.cost < two_opt.cost);
+                assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+            }
+        }
+    }
+
+    // 2-opt leaves only a few different tours of eight cities, the fewest the search
+    // runs on, so filling the population must give up instead of running forever
+    // without a time limit
+    #[test]
+    fn few_tours() {
+        let coords = vec![
+            (0., 0.),
+            (10., 0.),
+            (20., 0.),
+            (20., 10.),
+            (20., 20.),
+            (10., 20.),
+            (0., 20.),
+            (0., 10.),
+        ];
+        let metric = CoordMetric::new(coords, EdgeWeightType::Euc2d);
+        let optimum = held_karp::solver(&metric).cost;
+
+        for crossover in CROSSOVERS {
+            let solution = Pipeline::new(Greedy)
+                .then(GeneticAlgorithm::new().crossover(crossover))
+                .seed(0)
+                .solve(&metric);
+            assert_eq!(solution.cost, optimum, "{:?}", crossover);
+        }
+    }
+
+    // 2-opt keeps tours of asymmetric distances, so the memetic search still stops
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let greedy = Pipeline::new(Greedy).solve(&metric);
+
+        for crossover in CROSSOVERS {
+            let solution = Pipeline::new(Greedy)
+                .then(GeneticAlgorithm::new().crossover(crossover))
+                .seed(0)
+                .solve(&metric);
+            assert!(solution.cost <= greedy.cost, "{:?}", crossover);
+            assert_eq!(solution.cost, metric.tour_length(&solution.cities));
+
+            let mut sorted = solution.cities.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..metric.len()).collect::<Vec<usize>>());
+        }
+    }
+
+    #[test]
+    fn budget() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let initial = Greedy.construct(&instance, &mut NullObserver);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        // Without generations the best tour of the initial population is returned
+        let mut cities_idx = initial.clone();
+        let generations = GeneticAlgorithm::new().generations(0).evolve(
+            &mut NullObserver,
+            &instance,
+            &mut cities_idx,
+            &mut rng,
+            None,
+        );
+        assert_eq!(generations, 0);
+        let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+        assert!(instance.tour_length(&cities_idx) <= two_opt.cost);
+
+        // The best tour is returned when the time limit stops the search
+        let start = Instant::now();
+        let solution = Pipeline::new(Greedy)
+            .then(GeneticAlgorithm::new().generations(usize::MAX))
+            .time_limit(Duration::from_millis(300))
+            .solve(&instance);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(solution.cost < instance.tour_length(&initial));
+        assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+    }
+}
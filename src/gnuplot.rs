@@ -0,0 +1,197 @@
+use crate::observer::{Event, Observer};
+use std::io::{self, Write};
+use std::process::{Child, Command, Stdio};
+
+// Observer drawing the progress of solvers with Gnuplot.
+// If we enable gif output, then we can't see Gnuplot window
+// So if disable gif to see it.
+pub struct Gnuplot {
+    gp: Child,
+    cities: Vec<(f64, f64)>,
+    // Edges added so far by a construction heuristic
+    edges: Vec<(usize, usize)>,
+    // Draw every event. It is slow so only final images are drawn without this.
+    pub animate: bool,
+}
+
+impl Gnuplot {
+    pub fn new(cities: &[(f64, f64)], file_name: &str, enable_gif: bool) -> io::Result<Gnuplot> {
+        let gp = Command::new("gnuplot").stdin(Stdio::piped()).spawn()?;
+
+        let max_x: i32 = cities.iter().map(|t| t.0 as i32).max().unwrap_or(0);
+        let max_y: i32 = cities.iter().map(|t| t.1 as i32).max().unwrap_or(0);
+        let min_x: i32 = cities.iter().map(|t| t.0 as i32).min().unwrap_or(0);
+        let min_y: i32 = cities.iter().map(|t| t.1 as i32).min().unwrap_or(0);
+
+        // Slightly enlarge the x and y range covering the data.
+        let xrange = max_x - min_x;
+        let yrange = max_y - min_y;
+        let max_x = max_x + xrange / 7;
+        let min_x = min_x - xrange / 7;
+        let max_y = max_y + yrange / 7;
+        let min_y = min_y - yrange / 7;
+        let mut cmd = format!(
+            "set xrange [{}:{}]; set yrange [{}:{}]\n",
+            min_x, max_x, min_y, max_y
+        );
+
+        if enable_gif {
+            cmd.push_str(&format!(
+                "unset key; set term gif animate delay 1; \
+                    set output 'images/{}.gif\n",
+                file_name
+            ));
+        } else {
+            cmd.push_str("unset key\n");
+        }
+
+        let mut gnuplot = Gnuplot {
+            gp,
+            cities: cities.to_vec(),
+            edges: vec![],
+            animate: true,
+        };
+        gnuplot.write(&cmd)?;
+
+        Ok(gnuplot)
+    }
+
+    // Save final result of caluculated optimal pass as an image
+    pub fn save_image(&mut self, file_name: &str, cities_idx: &[usize]) -> io::Result<()> {
+        // let (terminal_type, file_type) = ("pdfcairo enhanced size 8in, 6in", "pdf");
+        let (terminal_type, file_type) = ("png", "png");
+        // Nothing is written if the path cannot be plotted
+        let plot = self.path_cmd(cities_idx, true)?;
+        let cmd = format!(
+            "set terminal {}; set output \"images/{}.{}\"\n",
+            terminal_type, file_name, file_type
+        );
+        self.write(&(cmd + &plot))
+    }
+
+    fn write(&mut self, cmd: &str) -> io::Result<()> {
+        self.gp.stdin.as_mut().unwrap().write_all(cmd.as_bytes())
+    }
+
+    // Coordinates of city, which EXPLICIT instances without DISPLAY_DATA_SECTION lack
+    fn city(&self, city: usize) -> io::Result<(f64, f64)> {
+        self.cities.get(city).copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("city {} has no coordinates to plot", city),
+            )
+        })
+    }
+
+    // Plot all cities in points and the path visiting cities_idx in lines
+    fn plot_path(&mut self, cities_idx: &[usize], closed: bool) -> io::Result<()> {
+        let cmd = self.path_cmd(cities_idx, closed)?;
+        self.write(&cmd)
+    }
+
+    fn path_cmd(&self, cities_idx: &[usize], closed: bool) -> io::Result<String> {
+        let mut cmd = "plot '-' with point pointtype 7 pointsize 2 linecolor rgb 'black', \
+            '-' with line linewidth 5 linetype 1 linecolor rgb 'cyan'\n"
+            .to_string();
+
+        for city in self.cities.iter() {
+            cmd.push_str(&format!("{} {}\n", city.0, city.1));
+        }
+        // End data input
+        cmd.push_str("e\n");
+
+        let last = if closed { cities_idx.first() } else { None };
+        for idx in cities_idx.iter().chain(last) {
+            let city = self.city(*idx)?;
+            cmd.push_str(&format!("{} {}\n", city.0, city.1));
+        }
+        cmd.push_str("e\n");
+
+        Ok(cmd)
+    }
+
+    // Plot all cities in points and edges in vectors
+    fn plot_edges(&mut self) -> io::Result<()> {
+        let mut cmd = "plot '-' with point pointtype 7 pointsize 2 linecolor rgb 'black', \
+            '-' using 1:2:($3-$1):($4-$2) with vectors lw 3 linetype 1 linecolor rgb 'cyan' nohead\n"
+            .to_string();
+
+        for city in self.cities.iter() {
+            cmd.push_str(&format!("{} {}\n", city.0, city.1));
+        }
+        cmd.push_str("e\n");
+
+        for (a, b) in self.edges.iter() {
+            let (a, b) = (self.city(*a)?, self.city(*b)?);
+            cmd.push_str(&format!("{} {} {} {}\n", a.0, a.1, b.0, b.1));
+        }
+        cmd.push_str("e\n");
+
+        self.write(&cmd)
+    }
+}
+
+impl Observer for Gnuplot {
+    fn notify(&mut self, event: &Event) {
+        // Instances without coordinates have nothing to draw
+        if !self.animate || self.cities.is_empty() {
+            return;
+        }
+
+        let result = match *event {
+            Event::EdgeAdded(a, b) => {
+                self.edges.push((a, b));
+                self.plot_edges()
+            }
+            Event::CityInserted { cities_idx, .. } => self.plot_path(cities_idx, false),
+            Event::TwoOptMove { cities_idx, .. }
+            | Event::OrOptMove { cities_idx, .. }
+            | Event::ThreeOptMove { cities_idx, .. }
+            | Event::LinKernighanMove { cities_idx, .. }
+            | Event::SwapMove { cities_idx, .. } => self.plot_path(cities_idx, true),
+            // Each move of local search is already drawn and bounds have no picture
+            Event::NewBest { .. } | Event::TabuBest { .. } | Event::LowerBound { .. } => return,
+        };
+        result.expect("failed to write to gnuplot");
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gnuplot() {
+        let cities = vec![(-50., -50.), (-50., 0.), (-50., 50.), (50., 50.)];
+        let file_name = "test_gnuplot";
+
+        let mut gp = Gnuplot::new(&cities, file_name, false).expect("failed to execute gnuplot");
+        gp.notify(&Event::EdgeAdded(0, 1));
+        gp.notify(&Event::CityInserted {
+            city: 2,
+            cities_idx: &[0, 1, 2],
+        });
+
+        gp.save_image(file_name, &[0, 1, 2, 3]).unwrap();
+    }
+
+    // Tours of instances without coordinates are reported as errors instead of panicking
+    #[test]
+    fn no_coordinates() {
+        let mut gp =
+            Gnuplot::new(&[], "test_no_coordinates", false).expect("failed to execute gnuplot");
+        gp.notify(&Event::EdgeAdded(0, 1));
+        gp.notify(&Event::TwoOptMove {
+            i: 0,
+            j: 1,
+            cities_idx: &[1, 0, 2],
+        });
+
+        let e = gp
+            .save_image("test_no_coordinates", &[0, 1, 2])
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,252 @@
+use rand::rngs::StdRng;
+
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+use crate::observer::Observer;
+use crate::solver::LocalSearch;
+use crate::two_opt::{self, NEIGHBORS};
+use std::time::Instant;
+
+// Weight of penalties relative to the average edge length of the first local optimum
+pub const ALPHA: f64 = 0.3;
+// Number of local optima penalized by default
+pub const ITERATIONS: usize = 1000;
+
+// Guided Local Search on top of 2-opt. When 2-opt stops at a local optimum, edges of the
+// tour with the largest length / (1 + penalty) are penalized and 2-opt runs again on the
+// lengths plus lambda * penalty, which pushes the tour out of the local optimum.
+// The tour shortest in the original lengths is returned.
+// Penalties are kept for all pairs of cities, which takes O(n^2) memory.
+#[derive(Debug, Clone)]
+pub struct GuidedLocalSearch {
+    alpha: f64,
+    iterations: usize,
+    neighbors: usize,
+}
+
+impl Default for GuidedLocalSearch {
+    fn default() -> GuidedLocalSearch {
+        GuidedLocalSearch {
+            alpha: ALPHA,
+            iterations: ITERATIONS,
+            neighbors: NEIGHBORS,
+        }
+    }
+}
+
+impl GuidedLocalSearch {
+    pub fn new() -> GuidedLocalSearch {
+        GuidedLocalSearch::default()
+    }
+
+    pub fn alpha(mut self, alpha: f64) -> GuidedLocalSearch {
+        self.alpha = alpha;
+        self
+    }
+
+    // Number of local optima penalized. The deadline of the pipeline also stops the search.
+    pub fn iterations(mut self, iterations: usize) -> GuidedLocalSearch {
+        self.iterations = iterations;
+        self
+    }
+
+    // Number of nearest neighbors of each city tried first by 2-opt
+    pub fn neighbors(mut self, k: usize) -> GuidedLocalSearch {
+        self.neighbors = k;
+        self
+    }
+}
+
+impl LocalSearch for GuidedLocalSearch {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        _: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        let neighbors = NeighborLists::new(metric, self.neighbors);
+        solver(
+            observer,
+            metric,
+            cities_idx,
+            &neighbors,
+            self.alpha,
+            self.iterations,
+            deadline,
+        );
+    }
+}
+
+// Edge lengths plus lambda times the number of times edges were penalized
+struct Augmented<'a, M: ?Sized> {
+    metric: &'a M,
+    penalties: &'a [Cost],
+    lambda: Cost,
+}
+
+impl<M: Metric + ?Sized> Metric for Augmented<'_, M> {
+    fn len(&self) -> usize {
+        self.metric.len()
+    }
+
+    fn dist(&self, i: usize, j: usize) -> Cost {
+        self.metric.dist(i, j) + self.lambda * self.penalties[i * self.metric.len() + j]
+    }
+}
+
+// Run Guided Local Search and leave the shortest tour found in cities_idx.
+// Returns the number of local optima penalized.
+pub fn solver<M: Metric + ?Sized>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    neighbors: &NeighborLists,
+    alpha: f64,
+    iterations: usize,
+    deadline: Option<Instant>,
+) -> usize {
+    let city_len = cities_idx.len();
+    let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+    two_opt::local_optimum(observer, metric, cities_idx, neighbors, false, deadline);
+    if city_len < 4 {
+        return 0;
+    }
+
+    let mut best = cities_idx.to_vec();
+    let mut best_cost = metric.tour_length(cities_idx);
+    let lambda = ((alpha * best_cost as f64 / city_len as f64).round() as Cost).max(1);
+    let mut penalties = vec![0; city_len * city_len];
+
+    let mut penalized = 0;
+    while penalized < iterations && !expired() {
+        penalized += 1;
+
+        // Penalize the edges with the largest utility
+        let utilities = (0..city_len)
+            .map(|i| {
+                let (a, b) = (cities_idx[i], cities_idx[(i + 1) % city_len]);
+                let utility = metric.dist(a, b) as f64 / (1 + penalties[a * city_len + b]) as f64;
+                (utility, a, b)
+            })
+            .collect::<Vec<(f64, usize, usize)>>();
+        let max = utilities
+            .iter()
+            .map(|(utility, ..)| *utility)
+            .fold(f64::MIN, f64::max);
+        for (utility, a, b) in utilities {
+            if utility == max {
+                penalties[a * city_len + b] += 1;
+                penalties[b * city_len + a] += 1;
+            }
+        }
+
+        let augmented = Augmented {
+            metric,
+            penalties: &penalties,
+            lambda,
+        };
+        two_opt::local_optimum(observer, &augmented, cities_idx, neighbors, false, deadline);
+
+        let cost = metric.tour_length(cities_idx);
+        if cost < best_cost {
+            best_cost = cost;
+            best.copy_from_slice(cities_idx);
+        }
+    }
+
+    cities_idx.copy_from_slice(&best);
+    penalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225},
+        greedy::Greedy,
+        observer::NullObserver,
+        solver::{Pipeline, Solver},
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+    use std::time::Duration;
+
+    // GLS escapes the local optimum where 2-opt stops
+    #[test]
+    fn escapes_local_optimum() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+            let gls = Pipeline::new(Greedy)
+                .then(GuidedLocalSearch::new().iterations(200))
+                .solve(&instance);
+
+            println!("{}: 2-opt {} gls {}", tsp_file, two_opt.cost, gls.cost);
+            assert!(gls.cost < two_opt.cost);
+            assert_eq!(gls.cost, instance.tour_length(&gls.cities));
+
+            let mut sorted = gls.cities.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..instance.len()).collect::<Vec<usize>>());
+        }
+    }
+
+    #[test]
+    fn budget() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let initial = Pipeline::new(Greedy).solve(&instance).cities;
+
+        // Without iterations it is just 2-opt
+        let mut cities_idx = initial.clone();
+        let iterations = solver(
+            &mut NullObserver,
+            &instance,
+            &mut cities_idx,
+            &neighbors,
+            ALPHA,
+            0,
+            None,
+        );
+        assert_eq!(iterations, 0);
+        let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+        assert_eq!(instance.tour_length(&cities_idx), two_opt.cost);
+
+        // Expired deadline stops before penalizing anything
+        let mut cities_idx = initial.clone();
+        let iterations = solver(
+            &mut NullObserver,
+            &instance,
+            &mut cities_idx,
+            &neighbors,
+            ALPHA,
+            ITERATIONS,
+            Some(Instant::now()),
+        );
+        assert_eq!(iterations, 0);
+
+        // The best tour is returned when the time limit stops the search
+        let start = Instant::now();
+        let solution = Pipeline::new(Greedy)
+            .then(GuidedLocalSearch::new().iterations(usize::MAX))
+            .time_limit(Duration::from_millis(200))
+            .solve(&instance);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(solution.cost <= two_opt.cost);
+        assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+    }
+
+    // 2-opt keeps tours of asymmetric distances, so GLS stops without a time limit
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let greedy = Pipeline::new(Greedy).solve(&metric);
+        let gls = Pipeline::new(Greedy)
+            .then(GuidedLocalSearch::new())
+            .solve(&metric);
+        assert_eq!(gls.cities, greedy.cities);
+    }
+}
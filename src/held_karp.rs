@@ -0,0 +1,350 @@
+use crate::matrix::DistanceMatrix;
+use crate::metric::{Cost, Metric};
+use crate::nearest_neighbor::NearestNeighbor;
+use crate::observer::Observer;
+use crate::solver::{ConstructionHeuristic, Solution};
+
+// Largest number of cities solved. The table takes 2^(n - 1) * (n - 1) costs,
+// which is 40 MB for 20 cities.
+pub const MAX_CITIES: usize = 20;
+
+// Shortest tour by Held-Karp dynamic programming.
+// It takes O(2^n * n^2) time and panics with more than MAX_CITIES cities.
+pub fn solver<M: Metric + ?Sized>(metric: &M) -> Solution {
+    let city_len = metric.len();
+    assert!(
+        city_len <= MAX_CITIES,
+        "Held-Karp solves up to {} cities but got {}",
+        MAX_CITIES,
+        city_len
+    );
+
+    let cities = if city_len <= 3 {
+        (0..city_len).collect()
+    } else {
+        held_karp_internal(&DistanceMatrix::from_metric(metric))
+    };
+    let cost = metric.tour_length(&cities);
+    Solution {
+        cities,
+        cost,
+        seed: None,
+    }
+}
+
+// Exact solver usable as the constructor of a pipeline.
+// Instances with more than MAX_CITIES cities get a nearest neighbor tour instead,
+// which is left to the improvers of the pipeline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeldKarp;
+
+impl ConstructionHeuristic for HeldKarp {
+    fn construct(&mut self, metric: &dyn Metric, observer: &mut dyn Observer) -> Vec<usize> {
+        if metric.len() > MAX_CITIES {
+            return NearestNeighbor.construct(metric, observer);
+        }
+        solver(metric).cities
+    }
+}
+
+fn held_karp_internal(matrix: &DistanceMatrix) -> Vec<usize> {
+    let city_len = matrix.dimension();
+    // Paths start from city 0 and bit i - 1 of a set stands for city i
+    let others = city_len - 1;
+    let full = (1 << others) - 1;
+
+    // Shortest path from city 0 visiting the cities of set and ending at city i,
+    // stored at set * others + i - 1
+    let mut table = vec![Cost::MAX; (1 << others) * others];
+    for i in 1..city_len {
+        table[(1 << (i - 1)) * others + i - 1] = matrix.get(0, i);
+    }
+
+    for set in 1..=full {
+        for i in 1..city_len {
+            let bit = 1 << (i - 1);
+            if set & bit == 0 || set == bit {
+                continue;
+            }
+
+            let rest = set ^ bit;
+            let mut shortest = Cost::MAX;
+            for j in 1..city_len {
+                let length = table[rest * others + j - 1];
+                if rest & (1 << (j - 1)) != 0 && length != Cost::MAX {
+                    shortest = shortest.min(length + matrix.get(j, i));
+                }
+            }
+            table[set * others + i - 1] = shortest;
+        }
+    }
+
+    // Walk back from the last city of the shortest tour
+    let length =
+        |set: usize, i: usize, next: usize| table[set * others + i - 1] + matrix.get(i, next);
+    let mut cities_idx = vec![];
+    let (mut set, mut next) = (full, 0);
+    while set != 0 {
+        let last = (1..city_len)
+            .filter(|i| set & (1 << (i - 1)) != 0)
+            .min_by_key(|i| length(set, *i, next))
+            .unwrap();
+        cities_idx.push(last);
+        set ^= 1 << (last - 1);
+        next = last;
+    }
+    cities_idx.push(0);
+    cities_idx.reverse();
+    cities_idx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ant_colony::{AntColony, Variant},
+        genetic_algorithm::{Crossover, GeneticAlgorithm},
+        greedy::Greedy,
+        guided_local_search::GuidedLocalSearch,
+        iterated_local_search::IteratedLocalSearch,
+        lin_kernighan::LinKernighan,
+        metric::{CoordMetric, EdgeWeightType, FnMetric},
+        nearest_insertion::NearestInsertion,
+        nearest_neighbor::NearestNeighbor,
+        or_opt::OrOpt,
+        simulated_annealing::SimulatedAnnealing,
+        solver::{LocalSearch, Pipeline, Solver},
+        tabu_search::TabuSearch,
+        three_opt::{MoveSet, ThreeOpt},
+        two_opt::{Strategy, TwoOpt},
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_instance(city_len: usize, rng: &mut StdRng) -> CoordMetric {
+        let coords = (0..city_len)
+            .map(|_| (rng.gen_range(0. ..1000.), rng.gen_range(0. ..1000.)))
+            .collect();
+        CoordMetric::new(coords, EdgeWeightType::Euc2d)
+    }
+
+    // Shortest tour by trying all permutations
+    fn brute_force<M: Metric>(metric: &M) -> Cost {
+        fn search<M: Metric>(metric: &M, path: &mut Vec<usize>, used: &mut [bool]) -> Cost {
+            if path.len() == metric.len() {
+                return metric.tour_length(path);
+            }
+            let mut shortest = Cost::MAX;
+            for city in 0..metric.len() {
+                if !used[city] {
+                    used[city] = true;
+                    path.push(city);
+                    shortest = shortest.min(search(metric, path, used));
+                    path.pop();
+                    used[city] = false;
+                }
+            }
+            shortest
+        }
+
+        let mut used = vec![false; metric.len()];
+        used[0] = true;
+        search(metric, &mut vec![0], &mut used)
+    }
+
+    #[test]
+    fn optimal() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for city_len in 1..=9 {
+            let metric = random_instance(city_len, &mut rng);
+            let solution = solver(&metric);
+
+            let mut sorted = solution.cities.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..city_len).collect::<Vec<usize>>());
+            assert_eq!(solution.cost, metric.tour_length(&solution.cities));
+            assert_eq!(solution.cost, brute_force(&metric), "{} cities", city_len);
+        }
+
+        // Asymmetric distances
+        let metric = FnMetric::new(6, |i, j| if (i + 1) % 6 == j { 1 } else { 10 });
+        let solution = solver(&metric);
+        assert_eq!(solution.cities, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(solution.cost, 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "Held-Karp solves up to")]
+    fn too_many_cities() {
+        let metric = FnMetric::new(MAX_CITIES + 1, |_, _| 1);
+        solver(&metric);
+    }
+
+    // Larger instances get a heuristic tour from the constructor instead of a panic
+    #[test]
+    fn constructor() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let metric = random_instance(MAX_CITIES + 10, &mut rng);
+        let solution = Pipeline::new(HeldKarp).solve(&metric);
+        let mut sorted = solution.cities.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..metric.len()).collect::<Vec<usize>>());
+        assert_eq!(
+            solution.cities,
+            Pipeline::new(NearestNeighbor).solve(&metric).cities
+        );
+
+        let metric = random_instance(12, &mut rng);
+        let solution = Pipeline::new(HeldKarp).solve(&metric);
+        assert_eq!(solution.cost, solver(&metric).cost);
+    }
+
+    // Gap of every heuristic from the optimum on random instances
+    #[test]
+    fn heuristics_gap() {
+        type Improver = fn() -> Box<dyn LocalSearch>;
+        // Largest gap of each improver after greedy in percent. Local searches may stop at
+        // a local optimum while metaheuristics should find the optimum or come close.
+        let improvers: [(&str, Improver, f64); 18] = [
+            ("2opt", || Box::new(TwoOpt::new()), 15.),
+            (
+                "2opt-best",
+                || Box::new(TwoOpt::new().strategy(Strategy::BestImprovement)),
+                15.,
+            ),
+            ("oropt", || Box::new(OrOpt::new()), 15.),
+            ("3opt", || Box::new(ThreeOpt::new()), 15.),
+            (
+                "or3opt",
+                || Box::new(ThreeOpt::new().moves(MoveSet::OrThreeOpt)),
+                15.,
+            ),
+            ("lk", || Box::new(LinKernighan::new()), 15.),
+            (
+                "gls",
+                || Box::new(GuidedLocalSearch::new().iterations(200)),
+                3.,
+            ),
+            (
+                "sa",
+                || Box::new(SimulatedAnnealing::new().iterations(1_000_000)),
+                3.,
+            ),
+            (
+                "ils",
+                || Box::new(IteratedLocalSearch::new(TwoOpt::new()).iterations(200)),
+                3.,
+            ),
+            ("tabu", || Box::new(TabuSearch::new().iterations(500)), 3.),
+            (
+                "ga",
+                || Box::new(GeneticAlgorithm::new().generations(20)),
+                3.,
+            ),
+            (
+                "ga-ox",
+                || {
+                    Box::new(
+                        GeneticAlgorithm::new()
+                            .crossover(Crossover::Order)
+                            .generations(20),
+                    )
+                },
+                3.,
+            ),
+            (
+                "ga-pmx",
+                || {
+                    Box::new(
+                        GeneticAlgorithm::new()
+                            .crossover(Crossover::PartiallyMapped)
+                            .generations(20),
+                    )
+                },
+                3.,
+            ),
+            (
+                "ga-erx",
+                || {
+                    Box::new(
+                        GeneticAlgorithm::new()
+                            .crossover(Crossover::EdgeRecombination)
+                            .generations(20),
+                    )
+                },
+                3.,
+            ),
+            ("aco", || Box::new(AntColony::new().iterations(200)), 5.),
+            (
+                "aco-2opt",
+                || Box::new(AntColony::new().iterations(50).two_opt(true)),
+                3.,
+            ),
+            (
+                "aco-mmas",
+                || {
+                    Box::new(
+                        AntColony::new()
+                            .variant(Variant::MaxMinAntSystem)
+                            .iterations(200),
+                    )
+                },
+                5.,
+            ),
+            (
+                "aco-mmas-2opt",
+                || {
+                    Box::new(
+                        AntColony::new()
+                            .variant(Variant::MaxMinAntSystem)
+                            .iterations(50)
+                            .two_opt(true),
+                    )
+                },
+                3.,
+            ),
+        ];
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..5 {
+            let city_len = rng.gen_range(10..=16);
+            let metric = random_instance(city_len, &mut rng);
+            let optimum = solver(&metric).cost;
+            let gap = |cost: Cost| {
+                assert!(cost >= optimum);
+                100. * (cost - optimum) as f64 / optimum as f64
+            };
+
+            let constructors = [
+                ("greedy", Pipeline::new(Greedy).solve(&metric).cost),
+                (
+                    "nearest_neighbor",
+                    Pipeline::new(NearestNeighbor).solve(&metric).cost,
+                ),
+                (
+                    "nearest_insertion",
+                    Pipeline::new(NearestInsertion).solve(&metric).cost,
+                ),
+            ];
+            // Nearest insertion is within twice the optimum and the others are
+            // not much worse on random instances
+            for (name, cost) in constructors {
+                println!("{} cities {}: {:.1}%", city_len, name, gap(cost));
+                assert!(gap(cost) <= 100., "{}", name);
+            }
+
+            for (name, improver, max_gap) in improvers.iter() {
+                let solution = Pipeline::new(Greedy)
+                    .then(improver())
+                    .seed(0)
+                    .solve(&metric);
+                println!(
+                    "{} cities greedy+{}: {:.1}%",
+                    city_len,
+                    name,
+                    gap(solution.cost)
+                );
+                assert!(gap(solution.cost) <= *max_gap, "{}", name);
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::metric::Metric;
+use crate::observer::Observer;
+use crate::solver::LocalSearch;
+use std::time::Instant;
+
+// Number of kicks by default
+pub const ITERATIONS: usize = 1000;
+
+// Which tour the next kick is applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Acceptance {
+    // Keep the best tour and kick it again
+    #[default]
+    Better,
+    // Always continue from the new local optimum
+    RandomWalk,
+    // Keep the current tour and kick it again, but start again from a random tour after
+    // the number of kicks in a row which do not improve the current tour
+    Restart(usize),
+}
+
+// Iterated Local Search. The tour is perturbed by a double-bridge kick, which cuts it
+// into four parts A B C D and reconnects them as A C B D, and the local search brings
+// it to a new local optimum. Local searches such as 2-opt cannot undo the kick easily
+// so the tour leaves the local optimum. The shortest tour found is returned.
+pub struct IteratedLocalSearch {
+    local_search: Box<dyn LocalSearch>,
+    acceptance: Acceptance,
+    iterations: usize,
+}
+
+impl IteratedLocalSearch {
+    pub fn new<L: LocalSearch + 'static>(local_search: L) -> IteratedLocalSearch {
+        IteratedLocalSearch {
+            local_search: Box::new(local_search),
+            acceptance: Acceptance::default(),
+            iterations: ITERATIONS,
+        }
+    }
+
+    pub fn acceptance(mut self, acceptance: Acceptance) -> IteratedLocalSearch {
+        self.acceptance = acceptance;
+        self
+    }
+
+    // Number of kicks. The deadline of the pipeline also stops the search.
+    pub fn iterations(mut self, iterations: usize) -> IteratedLocalSearch {
+        self.iterations = iterations;
+        self
+    }
+}
+
+impl LocalSearch for IteratedLocalSearch {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        rng: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+        // Neighbor lists of the local search are built once for all kicks
+        self.local_search.prepare(metric);
+        self.local_search
+            .improve(metric, cities_idx, deadline, rng, observer);
+        // Fewer than eight cities cannot be cut into four parts of two or more cities
+        if cities_idx.len() < 8 {
+            self.local_search.finish();
+            return;
+        }
+
+        let mut best = cities_idx.to_vec();
+        let mut best_cost = metric.tour_length(cities_idx);
+        // Tour the next kick is applied to
+        let mut current = best.clone();
+        let mut current_cost = best_cost;
+        let mut failures = 0;
+
+        for _ in 0..self.iterations {
+            if expired() {
+                break;
+            }
+
+            cities_idx.copy_from_slice(&current);
+            double_bridge(cities_idx, rng);
+            self.local_search
+                .improve(metric, cities_idx, deadline, rng, observer);
+
+            let cost = metric.tour_length(cities_idx);
+            if cost < best_cost {
+                best_cost = cost;
+                best.copy_from_slice(cities_idx);
+            }
+
+            match self.acceptance {
+                Acceptance::Better => {
+                    current.copy_from_slice(&best);
+                    current_cost = best_cost;
+                }
+                Acceptance::RandomWalk => {
+                    current.copy_from_slice(cities_idx);
+                    current_cost = cost;
+                }
+                Acceptance::Restart(n) => {
+                    if cost < current_cost {
+                        current.copy_from_slice(cities_idx);
+                        current_cost = cost;
+                        failures = 0;
+                        continue;
+                    }
+
+                    failures += 1;
+                    if failures >= n {
+                        failures = 0;
+                        current.shuffle(rng);
+                        self.local_search
+                            .improve(metric, &mut current, deadline, rng, observer);
+                        current_cost = metric.tour_length(&current);
+                        if current_cost < best_cost {
+                            best_cost = current_cost;
+                            best.copy_from_slice(&current);
+                        }
+                    }
+                }
+            }
+        }
+
+        cities_idx.copy_from_slice(&best);
+        self.local_search.finish();
+    }
+}
+
+// Cut the tour into A B C D at random positions and reconnect them as A C B D.
+// It replaces three edges without reversing any part of the tour.
+pub fn double_bridge<R: Rng>(cities_idx: &mut [usize], rng: &mut R) {
+    let city_len = cities_idx.len();
+    if city_len < 8 {
+        return;
+    }
+
+    // B and C start at p1 and p2 and D at p3, and every part has two or more cities
+    let p1 = rng.gen_range(2..city_len - 5);
+    let p2 = rng.gen_range(p1 + 2..city_len - 3);
+    let p3 = rng.gen_range(p2 + 2..city_len - 1);
+    cities_idx[p1..p3].rotate_left(p2 - p1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225},
+        greedy::Greedy,
+        lin_kernighan::LinKernighan,
+        metric::{CoordMetric, EdgeWeightType},
+        nearest_insertion::NearestInsertion,
+        nearest_neighbor::NearestNeighbor,
+        observer::NullObserver,
+        or_opt::OrOpt,
+        solver::{ConstructionHeuristic, Pipeline, Solver},
+        three_opt::ThreeOpt,
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+    use rand::SeedableRng;
+    use std::{cell::RefCell, rc::Rc, time::Duration};
+
+    #[test]
+    fn kick() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for city_len in [8, 9, 52] {
+            for _ in 0..1000 {
+                let mut cities_idx = (0..city_len).collect::<Vec<usize>>();
+                double_bridge(&mut cities_idx, &mut rng);
+
+                // Exactly three edges of the cycle are replaced
+                let mut changed = 0;
+                for i in 0..city_len {
+                    let (a, b) = (cities_idx[i], cities_idx[(i + 1) % city_len]);
+                    if (a + 1) % city_len != b {
+                        changed += 1;
+                    }
+                }
+                assert_eq!(changed, 3, "{:?}", cities_idx);
+            }
+        }
+    }
+
+    // ILS gets past the 2-opt local optimum from any constructor
+    #[test]
+    fn constructors() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100] {
+            let instance = Instance::load(tsp_file).unwrap();
+
+            for constructor in ["greedy", "nearest_neighbor", "nearest_insertion"] {
+                let pipeline = || match constructor {
+                    "greedy" => Pipeline::new(Greedy),
+                    "nearest_neighbor" => Pipeline::new(NearestNeighbor),
+                    _ => Pipeline::new(NearestInsertion),
+                };
+                let two_opt = pipeline().then(TwoOpt::new()).solve(&instance);
+                let ils = pipeline()
+                    .then(IteratedLocalSearch::new(TwoOpt::new()).iterations(200))
+                    .seed(0)
+                    .solve(&instance);
+
+                println!(
+                    "{} {}: 2-opt {} ils {}",
+                    tsp_file, constructor, two_opt.cost, ils.cost
+                );
+                assert!(ils.cost < two_opt.cost);
+                assert_eq!(ils.cost, instance.tour_length(&ils.cities));
+            }
+        }
+    }
+
+    #[test]
+    fn acceptance() {
+        let instance = Instance::load(TSP_FILE_TS225).unwrap();
+        let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+
+        for acceptance in [
+            Acceptance::Better,
+            Acceptance::RandomWalk,
+            Acceptance::Restart(20),
+        ] {
+            let solution = Pipeline::new(Greedy)
+                .then(
+                    IteratedLocalSearch::new(TwoOpt::new())
+                        .acceptance(acceptance)
+                        .iterations(100),
+                )
+                .seed(0)
+                .solve(&instance);
+            println!("{:?}: {}", acceptance, solution.cost);
+            assert!(solution.cost < two_opt.cost);
+        }
+    }
+
+    // After a restart the kicks are applied to the restarted tour instead of the best one
+    #[test]
+    fn restart() {
+        // Tours given to the local search, which changes nothing
+        struct Log(Rc<RefCell<Vec<Vec<usize>>>>);
+
+        impl LocalSearch for Log {
+            fn improve(
+                &mut self,
+                _: &dyn Metric,
+                cities_idx: &mut [usize],
+                _: Option<Instant>,
+                _: &mut StdRng,
+                _: &mut dyn Observer,
+            ) {
+                self.0.borrow_mut().push(cities_idx.to_vec());
+            }
+        }
+
+        // Number of edges of b which are not in a
+        fn changed(a: &[usize], b: &[usize]) -> usize {
+            let n = a.len();
+            let edge = |t: &[usize], i: usize| {
+                let (x, y) = (t[i], t[(i + 1) % n]);
+                (x.min(y), x.max(y))
+            };
+            let edges = (0..n).map(|i| edge(a, i)).collect::<Vec<_>>();
+            (0..n).filter(|i| !edges.contains(&edge(b, *i))).count()
+        }
+
+        // Every kick makes the cycle of cities on a circle longer
+        let city_len = 40;
+        let coords = (0..city_len)
+            .map(|i| {
+                let angle = 2. * std::f64::consts::PI * i as f64 / city_len as f64;
+                (1000. * angle.cos(), 1000. * angle.sin())
+            })
+            .collect::<Vec<(f64, f64)>>();
+        let metric = CoordMetric::new(coords, EdgeWeightType::Euc2d);
+        let optimum = (0..city_len).collect::<Vec<usize>>();
+
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut cities_idx = optimum.clone();
+        IteratedLocalSearch::new(Log(log.clone()))
+            .acceptance(Acceptance::Restart(2))
+            .iterations(30)
+            .improve(
+                &metric,
+                &mut cities_idx,
+                None,
+                &mut StdRng::seed_from_u64(0),
+                &mut NullObserver,
+            );
+        assert_eq!(cities_idx, optimum);
+
+        // The optimum is kicked twice, then the random tour of the restart is searched
+        let log = log.borrow();
+        assert_eq!(changed(&optimum, &log[1]), 3);
+        assert_eq!(changed(&optimum, &log[2]), 3);
+        for tour in &log[3..] {
+            assert!(changed(&optimum, tour) > 3);
+        }
+    }
+
+    // Kicks keep the direction of every part, so ILS still finds shorter tours of
+    // asymmetric distances while 2-opt keeps them
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let greedy = Pipeline::new(Greedy).solve(&metric);
+        let ils = Pipeline::new(Greedy)
+            .then(IteratedLocalSearch::new(TwoOpt::new()))
+            .seed(0)
+            .solve(&metric);
+        assert!(ils.cost <= greedy.cost);
+        assert_eq!(ils.cost, metric.tour_length(&ils.cities));
+
+        let mut sorted = ils.cities.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..metric.len()).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn deadline() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let mut cities_idx = Greedy.construct(&instance, &mut NullObserver);
+        let initial = instance.tour_length(&cities_idx);
+
+        let start = Instant::now();
+        IteratedLocalSearch::new(TwoOpt::new())
+            .iterations(usize::MAX)
+            .improve(
+                &instance,
+                &mut cities_idx,
+                Some(start + Duration::from_millis(200)),
+                &mut StdRng::seed_from_u64(0),
+                &mut NullObserver,
+            );
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(instance.tour_length(&cities_idx) < initial);
+    }
+
+    // Neighbor lists are built once for all kicks
+    #[test]
+    fn prepare() {
+        #[derive(Default)]
+        struct Calls(Rc<RefCell<Vec<&'static str>>>);
+
+        impl LocalSearch for Calls {
+            fn improve(
+                &mut self,
+                _: &dyn Metric,
+                _: &mut [usize],
+                _: Option<Instant>,
+                _: &mut StdRng,
+                _: &mut dyn Observer,
+            ) {
+                self.0.borrow_mut().push("improve");
+            }
+
+            fn prepare(&mut self, _: &dyn Metric) {
+                self.0.borrow_mut().push("prepare");
+            }
+
+            fn finish(&mut self) {
+                self.0.borrow_mut().push("finish");
+            }
+        }
+
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let calls = Calls::default();
+        let log = calls.0.clone();
+        Pipeline::new(Greedy)
+            .then(IteratedLocalSearch::new(calls).iterations(3))
+            .solve(&instance);
+        assert_eq!(
+            *log.borrow(),
+            ["prepare", "improve", "improve", "improve", "improve", "finish"]
+        );
+
+        // Prepared lists give the same tours as lists built by every call
+        fn same_tours<L: LocalSearch + Clone>(local_search: L, instance: &Instance) {
+            let initial = Greedy.construct(instance, &mut NullObserver);
+            let mut fresh = initial.clone();
+            local_search.clone().improve(
+                instance,
+                &mut fresh,
+                None,
+                &mut StdRng::seed_from_u64(0),
+                &mut NullObserver,
+            );
+            let mut prepared = initial;
+            let mut local_search = local_search;
+            local_search.prepare(instance);
+            local_search.improve(
+                instance,
+                &mut prepared,
+                None,
+                &mut StdRng::seed_from_u64(0),
+                &mut NullObserver,
+            );
+            local_search.finish();
+            assert_eq!(fresh, prepared);
+        }
+        same_tours(TwoOpt::new(), &instance);
+        same_tours(OrOpt::new(), &instance);
+        same_tours(ThreeOpt::new(), &instance);
+        same_tours(LinKernighan::new(), &instance);
+    }
+}
@@ -0,0 +1,48 @@
+// Heuristics for the symmetric traveling salesman problem on TSPLIB instances.
+//
+// Load an instance, pick a solver and save the tour:
+//
+//     use tsp::{greedy::Greedy, two_opt::TwoOpt, Instance, Pipeline, Solver};
+//
+//     let instance = Instance::load("berlin52.tsp.txt")?;
+//     let solution = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+//     solution.to_tour("berlin52.tour").save("berlin52.tour")?;
+#![cfg_attr(test, feature(test))]
+#[cfg(test)]
+extern crate test;
+
+pub mod ant_colony;
+pub mod bounds;
+pub mod branch_and_bound;
+pub mod christofides;
+#[cfg(test)]
+mod common;
+pub mod genetic_algorithm;
+pub mod gnuplot;
+pub mod greedy;
+pub mod guided_local_search;
+pub mod held_karp;
+pub mod iterated_local_search;
+pub mod lin_kernighan;
+pub mod matrix;
+pub mod metric;
+pub mod nearest_insertion;
+pub mod nearest_neighbor;
+pub mod neighbors;
+pub mod observer;
+pub mod or_opt;
+pub mod simulated_annealing;
+pub mod solver;
+pub mod tabu_search;
+pub mod three_opt;
+pub mod tour;
+pub mod tsplib;
+pub mod two_opt;
+pub mod unionfind;
+
+pub use matrix::DistanceMatrix;
+pub use metric::{Cost, Metric};
+pub use observer::{Event, Observer};
+pub use solver::{ConstructionHeuristic, LocalSearch, Pipeline, Solution, Solver};
+pub use tour::Tour;
+pub use tsplib::{Instance, ParseError, ParseErrorKind};
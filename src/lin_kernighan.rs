@@ -0,0 +1,555 @@
+use rand::rngs::StdRng;
+
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+use crate::observer::{Event, Observer};
+use crate::solver::LocalSearch;
+use crate::two_opt::NEIGHBORS;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// Largest number of edges exchanged by a move
+pub const MAX_DEPTH: usize = 50;
+// Number of alternatives tried at the first levels of a move before giving up
+pub const BREADTH: [usize; 2] = [5, 3];
+
+// Lin-Kernighan local search. A move removes an edge (t1, t2), adds an edge from t2 to a
+// nearest neighbor t3 and removes an edge of t3, and repeats it from the new end as long
+// as the removed edges are longer than the added ones in total. The longest prefix of
+// the sequence closing into a shorter tour is applied. Other candidates are tried at
+// the first levels when a sequence fails and cities are searched from only when their
+// tour neighbors have changed (don't-look bits).
+#[derive(Debug, Clone)]
+pub struct LinKernighan {
+    neighbors: usize,
+    max_depth: usize,
+    breadth: Vec<usize>,
+    // Neighbor lists kept between prepare and finish
+    prepared: Option<NeighborLists>,
+}
+
+impl Default for LinKernighan {
+    fn default() -> LinKernighan {
+        LinKernighan {
+            neighbors: NEIGHBORS,
+            max_depth: MAX_DEPTH,
+            breadth: BREADTH.to_vec(),
+            prepared: None,
+        }
+    }
+}
+
+impl LinKernighan {
+    pub fn new() -> LinKernighan {
+        LinKernighan::default()
+    }
+
+    // Number of nearest neighbors of each city tried as new edges
+    pub fn neighbors(mut self, k: usize) -> LinKernighan {
+        self.neighbors = k;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> LinKernighan {
+        self.max_depth = depth;
+        self
+    }
+
+    // Number of candidates tried at each level. Levels past the end try only the best one.
+    pub fn breadth(mut self, breadth: &[usize]) -> LinKernighan {
+        self.breadth = breadth.to_vec();
+        self
+    }
+}
+
+impl LocalSearch for LinKernighan {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        _: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        let neighbors = NeighborLists::prepared_or_new(&self.prepared, metric, self.neighbors);
+        local_optimum(
+            observer,
+            metric,
+            cities_idx,
+            &neighbors,
+            self.max_depth,
+            &self.breadth,
+            deadline,
+        );
+    }
+
+    fn prepare(&mut self, metric: &dyn Metric) {
+        self.prepared = Some(NeighborLists::new(metric, self.neighbors));
+    }
+
+    fn finish(&mut self) {
+        self.prepared = None;
+    }
+}
+
+// Apply improving moves until none is found and return the number of applied moves.
+// Tours of asymmetric metrics are left unchanged since gains assume the reversed parts of
+// the tour keep their length.
+pub fn local_optimum<M: Metric + ?Sized>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    neighbors: &NeighborLists,
+    max_depth: usize,
+    breadth: &[usize],
+    deadline: Option<Instant>,
+) -> usize {
+    let city_len = cities_idx.len();
+    // Every tour of three cities is optimal
+    if city_len < 4 || !neighbors.is_symmetric() {
+        return 0;
+    }
+
+    let mut search = Search {
+        metric,
+        neighbors,
+        tour: Tour::new(cities_idx),
+        max_depth,
+        breadth,
+        log: vec![],
+        added: vec![],
+        removed: vec![],
+        best_gain: 0,
+        best_len: 0,
+        best_removed: 0,
+    };
+
+    let mut queue = (0..city_len).collect::<VecDeque<usize>>();
+    let mut queued = vec![true; city_len];
+    let mut moves = 0;
+
+    while let Some(t1) = queue.pop_front() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
+        queued[t1] = false;
+
+        // Try both edges at t1 by turning the tour around
+        for _ in 0..2 {
+            if let Some(touched) = search.run(t1) {
+                moves += 1;
+                observer.notify(&Event::LinKernighanMove {
+                    edges: touched.len() / 2,
+                    cities_idx: search.tour.cities,
+                });
+                for city in touched {
+                    if !queued[city] {
+                        queued[city] = true;
+                        queue.push_back(city);
+                    }
+                }
+                break;
+            }
+            search.tour.reversed = !search.tour.reversed;
+        }
+    }
+
+    moves
+}
+
+// Cyclic tour in an array which can be read in either direction,
+// so that a path is reversed by reversing the shorter side
+struct Tour<'a> {
+    cities: &'a mut [usize],
+    pos: Vec<usize>,
+    reversed: bool,
+}
+
+impl<'a> Tour<'a> {
+    fn new(cities: &'a mut [usize]) -> Tour<'a> {
+        let mut pos = vec![0; cities.len()];
+        for (i, city) in cities.iter().enumerate() {
+            pos[*city] = i;
+        }
+        Tour {
+            cities,
+            pos,
+            reversed: false,
+        }
+    }
+
+    fn next(&self, city: usize, forward: bool) -> usize {
+        let n = self.cities.len();
+        if forward != self.reversed {
+            self.cities[(self.pos[city] + 1) % n]
+        } else {
+            self.cities[(self.pos[city] + n - 1) % n]
+        }
+    }
+
+    // Whether b is on the path from a to c
+    fn between(&self, a: usize, b: usize, c: usize) -> bool {
+        let n = self.cities.len();
+        let offset = |city: usize| {
+            if self.reversed {
+                (self.pos[a] + n - self.pos[city]) % n
+            } else {
+                (self.pos[city] + n - self.pos[a]) % n
+            }
+        };
+        offset(b) <= offset(c)
+    }
+
+    fn succ(&self, city: usize) -> usize {
+        self.next(city, true)
+    }
+
+    fn pred(&self, city: usize) -> usize {
+        self.next(city, false)
+    }
+
+    // Reverse the path from a to b
+    fn flip(&mut self, a: usize, b: usize) {
+        let n = self.cities.len();
+        let (from, to) = if self.reversed {
+            (self.pos[b], self.pos[a])
+        } else {
+            (self.pos[a], self.pos[b])
+        };
+        let len = (to + n - from) % n + 1;
+
+        if 2 * len > n {
+            // Reversing the rest gives the same cycle in the other direction
+            self.reverse((to + 1) % n, (from + n - 1) % n, n - len);
+            self.reversed = !self.reversed;
+        } else {
+            self.reverse(from, to, len);
+        }
+    }
+
+    // Reverse len cities from position i to position j
+    fn reverse(&mut self, mut i: usize, mut j: usize, len: usize) {
+        let n = self.cities.len();
+        for _ in 0..len / 2 {
+            self.cities.swap(i, j);
+            self.pos[self.cities[i]] = i;
+            self.pos[self.cities[j]] = j;
+            i = (i + 1) % n;
+            j = (j + n - 1) % n;
+        }
+    }
+}
+
+struct Search<'a, M: ?Sized> {
+    metric: &'a M,
+    neighbors: &'a NeighborLists,
+    tour: Tour<'a>,
+    max_depth: usize,
+    breadth: &'a [usize],
+    // Flips applied by the current move
+    log: Vec<(usize, usize)>,
+    added: Vec<(usize, usize)>,
+    removed: Vec<(usize, usize)>,
+    // Largest gain of closed tours and the numbers of flips and removed edges giving it
+    best_gain: Cost,
+    best_len: usize,
+    best_removed: usize,
+}
+
+fn contains(edges: &[(usize, usize)], (a, b): (usize, usize)) -> bool {
+    edges.contains(&(a, b)) || edges.contains(&(b, a))
+}
+
+impl<M: Metric + ?Sized> Search<'_, M> {
+    // Apply the best move removing the edge from t1 to its successor
+    // and return the ends of the removed edges
+    fn run(&mut self, t1: usize) -> Option<Vec<usize>> {
+        let t2 = self.tour.succ(t1);
+        self.best_gain = 0;
+        self.best_len = 0;
+        self.best_removed = 0;
+        self.added.clear();
+        self.removed.clear();
+        self.removed.push((t1, t2));
+
+        self.step(t1, 0, self.metric.dist(t1, t2));
+
+        // Undo flips after the best tour
+        while self.log.len() > self.best_len {
+            let (a, b) = self.log.pop().unwrap();
+            self.tour.flip(b, a);
+        }
+        if self.best_gain <= 0 {
+            return None;
+        }
+
+        self.log.clear();
+        // Cities lose an edge when their tour neighbors change
+        let touched = self.removed[..self.best_removed]
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .collect();
+        Some(touched)
+    }
+
+    // Extend the move where the edge from t1 to its successor is to be removed
+    // and gain is the total length of removed edges minus that of added edges.
+    // Returns true if an improving move is found.
+    fn step(&mut self, t1: usize, level: usize, gain: Cost) -> bool {
+        let t2 = self.tour.succ(t1);
+        let d = |a: usize, b: usize| self.metric.dist(a, b);
+
+        let mut candidates = vec![];
+        for t3 in self.neighbors.get(t2).iter().copied() {
+            let g1 = gain - d(t2, t3);
+            // Neighbors are sorted so the rest do not pass the gain criterion either
+            if g1 <= self.best_gain {
+                break;
+            }
+            if t3 == t1 || t3 == self.tour.succ(t2) || contains(&self.removed, (t2, t3)) {
+                continue;
+            }
+
+            // Removing (t4, t3) where t4 is the predecessor of t3 makes t4
+            // the new successor of t1 by reversing the path t2..t4
+            let t4 = self.tour.pred(t3);
+            if !contains(&self.added, (t4, t3)) {
+                candidates.push((g1 + d(t4, t3), Step::Flip { t3, t4 }));
+            }
+
+            // Removing (t3, t4) where t4 is the successor of t3 leaves the cycle t2..t3,
+            // which is joined again by adding (t4, t5) and removing (t5, t6) inside it.
+            // It is only tried first as in the original algorithm.
+            if level > 0 {
+                continue;
+            }
+            let t4 = self.tour.succ(t3);
+            let g2 = g1 + d(t3, t4);
+            for t5 in self.neighbors.get(t4).iter().copied() {
+                let g3 = g2 - d(t4, t5);
+                if g3 <= self.best_gain {
+                    break;
+                }
+                if t5 == t3 || !self.tour.between(t2, t5, t3) {
+                    continue;
+                }
+                let succ = self.tour.succ(t5);
+                candidates.push((g3 + d(t5, succ), Step::Exchange { t3, t4, t5 }));
+                if t5 != t2 {
+                    let pred = self.tour.pred(t5);
+                    candidates.push((g3 + d(t5, pred), Step::ReverseEach { t3, t4, t5 }));
+                }
+            }
+        }
+        // Prefer candidates removing long edges
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.0));
+
+        let breadth = self.breadth.get(level).copied().unwrap_or(1);
+        for (g, candidate) in candidates.into_iter().take(breadth) {
+            let (log_len, added_len, removed_len) =
+                (self.log.len(), self.added.len(), self.removed.len());
+            self.apply(t2, candidate);
+
+            let closed = g - d(self.tour.succ(t1), t1);
+            if closed > self.best_gain {
+                self.best_gain = closed;
+                self.best_len = self.log.len();
+                self.best_removed = self.removed.len();
+            }
+            if level + 1 < self.max_depth {
+                self.step(t1, level + 1, g);
+            }
+            if self.best_gain > 0 {
+                return true;
+            }
+
+            while self.log.len() > log_len {
+                let (a, b) = self.log.pop().unwrap();
+                self.tour.flip(b, a);
+            }
+            self.added.truncate(added_len);
+            self.removed.truncate(removed_len);
+        }
+
+        false
+    }
+
+    // Apply candidate after the edge (t1, t2) leaving the end of the new path
+    // as the successor of t1
+    fn apply(&mut self, t2: usize, candidate: Step) {
+        match candidate {
+            Step::Flip { t3, t4 } => {
+                self.flip(t2, t4);
+                self.added.push((t2, t3));
+                self.removed.push((t4, t3));
+            }
+            // t1 t2..t5 t6..t3 t4 becomes t1 t6..t3 t2..t5 t4
+            Step::Exchange { t3, t4, t5 } => {
+                let t6 = self.tour.succ(t5);
+                self.flip(t2, t3);
+                self.flip(t3, t6);
+                self.flip(t5, t2);
+                self.added.extend([(t2, t3), (t4, t5)]);
+                self.removed.extend([(t3, t4), (t5, t6)]);
+            }
+            // t1 t2..t6 t5..t3 t4 becomes t1 t6..t2 t3..t5 t4
+            Step::ReverseEach { t3, t4, t5 } => {
+                let t6 = self.tour.pred(t5);
+                self.flip(t2, t6);
+                self.flip(t5, t3);
+                self.added.extend([(t2, t3), (t4, t5)]);
+                self.removed.extend([(t3, t4), (t5, t6)]);
+            }
+        }
+    }
+
+    fn flip(&mut self, a: usize, b: usize) {
+        self.tour.flip(a, b);
+        self.log.push((a, b));
+    }
+}
+
+// Ways to extend a move by one or two edges
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Flip { t3: usize, t4: usize },
+    Exchange { t3: usize, t4: usize, t5: usize },
+    ReverseEach { t3: usize, t4: usize, t5: usize },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225},
+        greedy::Greedy,
+        metric::FnMetric,
+        nearest_neighbor::NearestNeighbor,
+        observer::NullObserver,
+        solver::{ConstructionHeuristic, Pipeline, Solver},
+        tsplib::Instance,
+    };
+
+    // Cycle read from city 0 in the current direction
+    fn read(tour: &Tour) -> Vec<usize> {
+        let mut cities = vec![0];
+        while cities.len() < tour.cities.len() {
+            cities.push(tour.succ(*cities.last().unwrap()));
+        }
+        cities
+    }
+
+    #[test]
+    fn flip() {
+        let mut cities = (0..8).collect::<Vec<usize>>();
+        let mut tour = Tour::new(&mut cities);
+
+        tour.flip(1, 2);
+        assert_eq!(read(&tour), [0, 2, 1, 3, 4, 5, 6, 7]);
+        assert!(!tour.reversed);
+
+        // The rest is reversed instead of the long path
+        tour.flip(3, 7);
+        assert_eq!(read(&tour), [0, 2, 1, 7, 6, 5, 4, 3]);
+        assert!(tour.reversed);
+
+        // Paths are read in the current direction
+        tour.flip(6, 4);
+        assert_eq!(read(&tour), [0, 2, 1, 7, 4, 5, 6, 3]);
+        for (i, city) in tour.cities.iter().enumerate() {
+            assert_eq!(tour.pos[*city], i);
+        }
+        assert_eq!(tour.pred(0), 3);
+    }
+
+    #[test]
+    fn local_optimum() {
+        // Optimal tour lengths from TSPLIB
+        for (tsp_file, optimum) in [
+            (TSP_FILE_BERLIN52, 7542),
+            (TSP_FILE_KROC100, 20749),
+            (TSP_FILE_TS225, 126643),
+        ] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+
+            for mut constructor in [
+                Box::new(Greedy) as Box<dyn ConstructionHeuristic>,
+                Box::new(NearestNeighbor),
+            ] {
+                let mut cities_idx = constructor.construct(&instance, &mut NullObserver);
+                let initial = instance.tour_length(&cities_idx);
+
+                let mut gains = vec![];
+                let mut last = initial;
+                let mut observer = |event: &Event| {
+                    if let Event::LinKernighanMove { cities_idx, .. } = event {
+                        let cost = instance.tour_length(cities_idx);
+                        gains.push(last - cost);
+                        last = cost;
+                    }
+                };
+                let moves = super::local_optimum(
+                    &mut observer,
+                    &instance,
+                    &mut cities_idx,
+                    &neighbors,
+                    MAX_DEPTH,
+                    &BREADTH,
+                    None,
+                );
+
+                // Every move shortens the tour
+                assert_eq!(gains.len(), moves);
+                assert!(gains.iter().all(|gain| *gain > 0));
+
+                let cost = instance.tour_length(&cities_idx);
+                println!("{}: {} -> {} ({} moves)", tsp_file, initial, cost, moves);
+                assert!((cost as f64) < optimum as f64 * 1.02);
+
+                let mut sorted = cities_idx.clone();
+                sorted.sort();
+                assert_eq!(sorted, (0..instance.len()).collect::<Vec<usize>>());
+            }
+        }
+    }
+
+    #[test]
+    fn small() {
+        let x = [0, 10, 1, 11, 2];
+        let metric = FnMetric::new(5, |i, j| (x[i] as Cost - x[j] as Cost).abs());
+        let neighbors = NeighborLists::new(&metric, 4);
+        let mut cities_idx = vec![0, 1, 2, 3, 4];
+        super::local_optimum(
+            &mut NullObserver,
+            &metric,
+            &mut cities_idx,
+            &neighbors,
+            MAX_DEPTH,
+            &BREADTH,
+            None,
+        );
+        assert_eq!(metric.tour_length(&cities_idx), 22);
+    }
+
+    #[test]
+    fn pipeline() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let greedy = Pipeline::new(Greedy).solve(&instance);
+        let solution = Pipeline::new(Greedy)
+            .then(LinKernighan::new())
+            .solve(&instance);
+        assert!(solution.cost < greedy.cost);
+    }
+
+    // Gains are wrong for asymmetric distances, so the tour is kept
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let greedy = Pipeline::new(Greedy).solve(&metric);
+        let solution = Pipeline::new(Greedy)
+            .then(LinKernighan::new())
+            .solve(&metric);
+        assert_eq!(solution.cities, greedy.cities);
+    }
+}
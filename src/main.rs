@@ -1,11 +1,505 @@
-#![feature(test)]
-extern crate test;
+use std::time::Duration;
+use tsp::{
+    ant_colony::{AntColony, Variant},
+    bounds,
+    branch_and_bound::BranchAndBound,
+    christofides::Christofides,
+    genetic_algorithm::{Crossover, GeneticAlgorithm},
+    greedy::Greedy,
+    guided_local_search::GuidedLocalSearch,
+    held_karp::{self, HeldKarp},
+    iterated_local_search::{Acceptance, IteratedLocalSearch},
+    lin_kernighan::LinKernighan,
+    nearest_insertion::NearestInsertion,
+    nearest_neighbor::NearestNeighbor,
+    or_opt::OrOpt,
+    simulated_annealing::{Cooling, SimulatedAnnealing},
+    tabu_search::TabuSearch,
+    three_opt::{MoveCounts, MoveSet, ThreeOpt},
+    two_opt::{Strategy, TwoOpt},
+    Cost, Event, Instance, Metric, Pipeline, Solver,
+};
 
-mod common;
-mod greedy;
-mod nearest_insertion;
-mod nearest_neighbor;
-mod two_opt;
-mod unionfind;
+const USAGE: &str = "\
+Usage: tsp solve --input <FILE> [OPTIONS]
 
-fn main() {}
+Options:
+    --algo <ALGO>          Solver to run (default: greedy+2opt).
+                           greedy, nearest_neighbor, nearest_insertion,
+                           christofides,
+                           held_karp (optimal, up to 20 cities)
+                           or branch_and_bound (optimal unless stopped)
+                           followed by any number of improvers
+                           +2opt, +2opt-best, +2opt-random, +oropt,
+                           +3opt, +or3opt, +lk, +gls, +sa, +sa-linear,
+                           +sa-adaptive, +ils, +ils-walk, +ils-restart,
+                           +tabu, +ga, +ga-ox, +ga-pmx, +ga-erx, +aco,
+                           +aco-2opt, +aco-mmas or +aco-mmas-2opt
+    --input <FILE>         TSPLIB instance to solve
+    --output <FILE>        Write the tour in TSPLIB .tour format
+    --seed <N>             Seed of random numbers used by improvers.
+                           A random seed is chosen and printed without it
+    --time-limit <TIME>    Stop branch_and_bound and improvers after TIME
+                           such as 5s, 500ms or 2m
+    --lower-bound          Print the Held-Karp lower bound of the optimum
+                           and the gap of the tour after solving.
+                           Skipped for asymmetric instances
+    -h, --help             Print this message
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Constructor {
+    Greedy,
+    NearestNeighbor,
+    NearestInsertion,
+    Christofides,
+    HeldKarp,
+    BranchAndBound,
+}
+
+// Variants are named after the improvers they build
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Improver {
+    TwoOpt(Strategy),
+    OrOpt,
+    ThreeOpt(MoveSet),
+    LinKernighan,
+    GuidedLocalSearch,
+    SimulatedAnnealing(Cooling),
+    IteratedLocalSearch(Acceptance),
+    TabuSearch,
+    GeneticAlgorithm(Crossover),
+    // Variant and whether 2-opt improves tours of ants
+    AntColony(Variant, bool),
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    constructor: Constructor,
+    improvers: Vec<Improver>,
+    input: String,
+    output: Option<String>,
+    seed: Option<u64>,
+    time_limit: Option<Duration>,
+    lower_bound: bool,
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = solve(&options) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn solve(options: &Options) -> Result<(), String> {
+    let instance =
+        Instance::load(&options.input).map_err(|e| format!("{}: {}", options.input, e))?;
+
+    let now = std::time::Instant::now();
+
+    let mut pipeline = match options.constructor {
+        Constructor::Greedy => Pipeline::new(Greedy),
+        Constructor::NearestNeighbor => Pipeline::new(NearestNeighbor),
+        Constructor::NearestInsertion => Pipeline::new(NearestInsertion),
+        Constructor::Christofides => Pipeline::new(Christofides),
+        Constructor::HeldKarp => {
+            if instance.len() > held_karp::MAX_CITIES {
+                return Err(format!(
+                    "held_karp solves up to {} cities but {} has {}",
+                    held_karp::MAX_CITIES,
+                    options.input,
+                    instance.len()
+                ));
+            }
+            Pipeline::new(HeldKarp)
+        }
+        Constructor::BranchAndBound => {
+            let mut bb = BranchAndBound::new();
+            if let Some(time_limit) = options.time_limit {
+                bb = bb.time_limit(time_limit);
+            }
+            Pipeline::new(bb)
+        }
+    };
+    for improver in options.improvers.iter() {
+        pipeline = match improver {
+            Improver::TwoOpt(strategy) => pipeline.then(TwoOpt::new().strategy(*strategy)),
+            Improver::OrOpt => pipeline.then(OrOpt::new()),
+            Improver::ThreeOpt(moves) => pipeline.then(ThreeOpt::new().moves(*moves)),
+            Improver::LinKernighan => pipeline.then(LinKernighan::new()),
+            Improver::GuidedLocalSearch => pipeline.then(GuidedLocalSearch::new()),
+            Improver::SimulatedAnnealing(cooling) => {
+                pipeline.then(SimulatedAnnealing::new().cooling(*cooling))
+            }
+            Improver::IteratedLocalSearch(acceptance) => {
+                pipeline.then(IteratedLocalSearch::new(TwoOpt::new()).acceptance(*acceptance))
+            }
+            Improver::TabuSearch => pipeline.then(TabuSearch::new()),
+            Improver::GeneticAlgorithm(crossover) => {
+                pipeline.then(GeneticAlgorithm::new().crossover(*crossover))
+            }
+            Improver::AntColony(variant, two_opt) => {
+                pipeline.then(AntColony::new().variant(*variant).two_opt(*two_opt))
+            }
+        };
+    }
+    if let Some(time_limit) = options.time_limit {
+        pipeline = pipeline.time_limit(time_limit);
+    }
+    if let Some(seed) = options.seed {
+        pipeline = pipeline.seed(seed);
+    }
+
+    let mut counts = MoveCounts::default();
+    let mut bound = None;
+    let mut trace = vec![];
+    let solution = pipeline.solve_observed(&instance, &mut |event: &Event| match event {
+        Event::ThreeOptMove { reconnection, .. } => counts.add(*reconnection),
+        Event::TabuBest { iteration, cost } => trace.push(format!("{}: {}", iteration, cost)),
+        Event::LowerBound { lower_bound, .. } => bound = Some(*lower_bound),
+        _ => {}
+    });
+    println!(
+        "Total distance: {}, Elapsed time: {} ms",
+        solution.cost,
+        now.elapsed().as_millis()
+    );
+    // Passing the seed to --seed reproduces the same tour
+    if let Some(seed) = solution.seed {
+        println!("Seed: {}", seed);
+    }
+    if counts.total() > 0 {
+        println!("3-opt moves: {}", counts);
+    }
+    // Best length after each number of moves
+    if !trace.is_empty() {
+        println!("Tabu search trace: {}", trace.join(", "));
+    }
+    // Bound of branch-and-bound or Held-Karp bound, whichever is higher.
+    // Improvers may have shortened the tour of the constructor.
+    if options.lower_bound {
+        match solution.lower_bound(&instance) {
+            Some(held_karp) => {
+                bound = Some(bound.map_or(held_karp, |bound: Cost| bound.max(held_karp)))
+            }
+            None => println!("Lower bound: skipped for asymmetric instance"),
+        }
+    }
+    if let Some(bound) = bound {
+        println!(
+            "Lower bound: {}, Gap: {:.2}%",
+            bound,
+            100. * bounds::gap(solution.cost, bound)
+        );
+    }
+
+    if let Some(output) = &options.output {
+        solution
+            .to_tour(&format!("{}.tour", instance.name))
+            .save(output)
+            .map_err(|e| format!("{}: {}", output, e))?;
+    }
+
+    Ok(())
+}
+
+// Returns None if help is requested
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        return Ok(None);
+    }
+
+    match args.first().map(|arg| arg.as_str()) {
+        Some("solve") => {}
+        Some(command) => return Err(format!("unknown command {:?}", command)),
+        None => return Err("no command is given".to_string()),
+    }
+
+    let mut options = Options {
+        constructor: Constructor::Greedy,
+        improvers: vec![Improver::TwoOpt(Strategy::FirstImprovement)],
+        input: String::new(),
+        output: None,
+        seed: None,
+        time_limit: None,
+        lower_bound: false,
+    };
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        if arg == "--lower-bound" {
+            options.lower_bound = true;
+            continue;
+        }
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+
+        match arg.as_str() {
+            "--algo" => {
+                let (constructor, improvers) = parse_algo(value()?)?;
+                options.constructor = constructor;
+                options.improvers = improvers;
+            }
+            "--input" => options.input = value()?.clone(),
+            "--output" => options.output = Some(value()?.clone()),
+            "--seed" => {
+                let seed = value()?;
+                options.seed = Some(
+                    seed.parse()
+                        .map_err(|_| format!("invalid seed {:?}", seed))?,
+                );
+            }
+            "--time-limit" => options.time_limit = Some(parse_duration(value()?)?),
+            _ => return Err(format!("unknown option {:?}", arg)),
+        }
+    }
+
+    if options.input.is_empty() {
+        return Err("--input is required".to_string());
+    }
+
+    Ok(Some(options))
+}
+
+// Parse constructor and improvers such as greedy+2opt
+fn parse_algo(algo: &str) -> Result<(Constructor, Vec<Improver>), String> {
+    let mut names = algo.split('+');
+
+    let constructor = match names.next() {
+        Some("greedy") => Constructor::Greedy,
+        Some("nearest_neighbor") => Constructor::NearestNeighbor,
+        Some("nearest_insertion") => Constructor::NearestInsertion,
+        Some("christofides") => Constructor::Christofides,
+        Some("held_karp") => Constructor::HeldKarp,
+        Some("branch_and_bound") => Constructor::BranchAndBound,
+        _ => return Err(format!("unknown algorithm {:?}", algo)),
+    };
+
+    let improvers = names
+        .map(|name| match name {
+            "2opt" => Ok(Improver::TwoOpt(Strategy::FirstImprovement)),
+            "2opt-best" => Ok(Improver::TwoOpt(Strategy::BestImprovement)),
+            "2opt-random" => Ok(Improver::TwoOpt(Strategy::Random)),
+            "oropt" => Ok(Improver::OrOpt),
+            "3opt" => Ok(Improver::ThreeOpt(MoveSet::Full)),
+            "or3opt" => Ok(Improver::ThreeOpt(MoveSet::OrThreeOpt)),
+            "lk" => Ok(Improver::LinKernighan),
+            "gls" => Ok(Improver::GuidedLocalSearch),
+            "sa" => Ok(Improver::SimulatedAnnealing(Cooling::default())),
+            "sa-linear" => Ok(Improver::SimulatedAnnealing(Cooling::Linear)),
+            "sa-adaptive" => Ok(Improver::SimulatedAnnealing(Cooling::Adaptive {
+                rate: 0.999999,
+                stall: 1_000_000,
+                reheat: 0.5,
+            })),
+            "ils" => Ok(Improver::IteratedLocalSearch(Acceptance::Better)),
+            "ils-walk" => Ok(Improver::IteratedLocalSearch(Acceptance::RandomWalk)),
+            "ils-restart" => Ok(Improver::IteratedLocalSearch(Acceptance::Restart(100))),
+            "tabu" => Ok(Improver::TabuSearch),
+            "ga" => Ok(Improver::GeneticAlgorithm(Crossover::EdgeAssembly)),
+            "ga-ox" => Ok(Improver::GeneticAlgorithm(Crossover::Order)),
+            "ga-pmx" => Ok(Improver::GeneticAlgorithm(Crossover::PartiallyMapped)),
+            "ga-erx" => Ok(Improver::GeneticAlgorithm(Crossover::EdgeRecombination)),
+            "aco" => Ok(Improver::AntColony(Variant::AntColonySystem, false)),
+            "aco-2opt" => Ok(Improver::AntColony(Variant::AntColonySystem, true)),
+            "aco-mmas" => Ok(Improver::AntColony(Variant::MaxMinAntSystem, false)),
+            "aco-mmas-2opt" => Ok(Improver::AntColony(Variant::MaxMinAntSystem, true)),
+            _ => Err(format!("unknown improver {:?} in {:?}", name, algo)),
+        })
+        .collect::<Result<Vec<Improver>, String>>()?;
+
+    Ok((constructor, improvers))
+}
+
+// Parse time such as 5s, 500ms, 2m or 1.5 (seconds)
+fn parse_duration(time: &str) -> Result<Duration, String> {
+    let (number, scale) = if let Some(ms) = time.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(s) = time.strip_suffix('s') {
+        (s, 1.)
+    } else if let Some(m) = time.strip_suffix('m') {
+        (m, 60.)
+    } else {
+        (time, 1.)
+    };
+
+    match number.parse::<f64>() {
+        Ok(n) if n >= 0. && n.is_finite() => Ok(Duration::from_secs_f64(n * scale)),
+        _ => Err(format!("invalid time {:?}", time)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_solve() {
+        let options = parse_args(&args(
+            "solve --algo nearest_insertion+2opt --input foo.tsp --output foo.tour \
+            --seed 42 --time-limit 5s --lower-bound",
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                constructor: Constructor::NearestInsertion,
+                improvers: vec![Improver::TwoOpt(Strategy::FirstImprovement)],
+                input: "foo.tsp".to_string(),
+                output: Some("foo.tour".to_string()),
+                seed: Some(42),
+                time_limit: Some(Duration::from_secs(5)),
+                lower_bound: true,
+            }
+        );
+
+        let options = parse_args(&args("solve --input foo.tsp --algo greedy"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.constructor, Constructor::Greedy);
+        assert!(options.improvers.is_empty());
+
+        let options = parse_args(&args("solve --input foo.tsp --algo held_karp"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.constructor, Constructor::HeldKarp);
+
+        let options = parse_args(&args("solve --input foo.tsp --algo branch_and_bound+2opt"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.constructor, Constructor::BranchAndBound);
+
+        let options = parse_args(&args("solve --input foo.tsp --algo christofides+lk"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.constructor, Constructor::Christofides);
+        assert_eq!(options.improvers, vec![Improver::LinKernighan]);
+
+        let options = parse_args(&args(
+            "solve --input foo.tsp --algo nearest_neighbor+2opt-random+2opt-best+oropt+3opt+or3opt+lk+gls+sa-linear+ils-walk+tabu+ga-pmx+aco-mmas-2opt",
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            options.improvers,
+            vec![
+                Improver::TwoOpt(Strategy::Random),
+                Improver::TwoOpt(Strategy::BestImprovement),
+                Improver::OrOpt,
+                Improver::ThreeOpt(MoveSet::Full),
+                Improver::ThreeOpt(MoveSet::OrThreeOpt),
+                Improver::LinKernighan,
+                Improver::GuidedLocalSearch,
+                Improver::SimulatedAnnealing(Cooling::Linear),
+                Improver::IteratedLocalSearch(Acceptance::RandomWalk),
+                Improver::TabuSearch,
+                Improver::GeneticAlgorithm(Crossover::PartiallyMapped),
+                Improver::AntColony(Variant::MaxMinAntSystem, true),
+            ]
+        );
+        assert_eq!(options.output, None);
+        assert!(!options.lower_bound);
+    }
+
+    #[test]
+    fn parse_error() {
+        assert!(parse_args(&args("")).is_err());
+        assert!(parse_args(&args("run --input foo.tsp")).is_err());
+        assert!(parse_args(&args("solve")).is_err());
+        assert!(parse_args(&args("solve --input")).is_err());
+        assert!(parse_args(&args("solve --input foo.tsp --algo 3opt")).is_err());
+        assert!(parse_args(&args("solve --input foo.tsp --algo greedy+4opt")).is_err());
+        assert!(parse_args(&args("solve --input foo.tsp --seed -1")).is_err());
+        assert!(parse_args(&args("solve --input foo.tsp --verbose")).is_err());
+        assert_eq!(parse_args(&args("solve --help")).unwrap(), None);
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+
+    #[test]
+    fn solve_berlin() {
+        let output = std::env::temp_dir().join("tsp_solve_berlin.tour");
+        let options = Options {
+            constructor: Constructor::Greedy,
+            improvers: vec![Improver::TwoOpt(Strategy::FirstImprovement)],
+            input: "berlin52.tsp.txt".to_string(),
+            output: Some(output.to_str().unwrap().to_string()),
+            seed: Some(42),
+            time_limit: Some(Duration::from_millis(100)),
+            lower_bound: true,
+        };
+        solve(&options).unwrap();
+
+        let tour = tsp::Tour::load(&output).unwrap();
+        assert_eq!(tour.name, "berlin52.tour");
+        assert_eq!(tour.cities.len(), 52);
+        std::fs::remove_file(output).unwrap();
+
+        // Too many cities for the exact solver
+        let options = Options {
+            constructor: Constructor::HeldKarp,
+            improvers: vec![],
+            output: None,
+            ..options
+        };
+        assert!(solve(&options).is_err());
+
+        // The exact solver stops at the time limit
+        let options = Options {
+            constructor: Constructor::BranchAndBound,
+            ..options
+        };
+        solve(&options).unwrap();
+    }
+
+    // Lower bounds assume symmetric distances and are skipped otherwise
+    #[test]
+    fn solve_asymmetric() {
+        let input = std::env::temp_dir().join("tsp_solve_asymmetric.tsp");
+        std::fs::write(
+            &input,
+            "NAME: asymmetric\nTYPE: TSP\nDIMENSION: 4\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+            EDGE_WEIGHT_FORMAT: FULL_MATRIX\nEDGE_WEIGHT_SECTION\n\
+            0 1 9 9\n9 0 1 9\n9 9 0 1\n1 9 9 0\nEOF\n",
+        )
+        .unwrap();
+        let options = Options {
+            constructor: Constructor::Greedy,
+            improvers: vec![],
+            input: input.to_str().unwrap().to_string(),
+            output: None,
+            seed: Some(42),
+            time_limit: None,
+            lower_bound: true,
+        };
+        solve(&options).unwrap();
+        std::fs::remove_file(input).unwrap();
+    }
+}
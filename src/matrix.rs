@@ -0,0 +1,80 @@
+use crate::metric::{Cost, Metric};
+
+// Dense n x n matrix of distances between cities
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceMatrix {
+    dimension: usize,
+    weights: Vec<Cost>,
+}
+
+impl DistanceMatrix {
+    pub fn new(dimension: usize) -> DistanceMatrix {
+        DistanceMatrix {
+            dimension,
+            weights: vec![
+                0;
+                dimension
+                    .checked_mul(dimension)
+                    .expect("distance matrix dimension overflows")
+            ],
+        }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> Cost>(dimension: usize, f: F) -> DistanceMatrix {
+        let mut matrix = DistanceMatrix::new(dimension);
+        for i in 0..dimension {
+            for j in 0..dimension {
+                matrix.weights[i * dimension + j] = f(i, j);
+            }
+        }
+        matrix
+    }
+
+    // Precompute distances of any metric, which makes lookups of lazy metrics cheap
+    pub fn from_metric<M: Metric + ?Sized>(metric: &M) -> DistanceMatrix {
+        DistanceMatrix::from_fn(metric.len(), |i, j| metric.dist(i, j))
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> Cost {
+        self.weights[i * self.dimension + j]
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, weight: Cost) {
+        self.weights[i * self.dimension + j] = weight;
+    }
+}
+
+impl Metric for DistanceMatrix {
+    fn len(&self) -> usize {
+        self.dimension
+    }
+
+    fn dist(&self, i: usize, j: usize) -> Cost {
+        self.get(i, j)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        let mut m = DistanceMatrix::from_fn(3, |i, j| (i * 3 + j) as Cost);
+        assert_eq!(m.dimension(), 3);
+        assert_eq!(m.get(0, 2), 2);
+        assert_eq!(m.get(2, 0), 6);
+
+        m.set(2, 0, 10);
+        assert_eq!(m.get(2, 0), 10);
+        assert_eq!(m.get(0, 2), 2);
+
+        let m = DistanceMatrix::from_metric(&m);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.dist(2, 0), 10);
+    }
+}
@@ -0,0 +1,292 @@
+use std::str::FromStr;
+
+// Length of an edge or a tour
+pub type Cost = i32;
+
+// Distances between cities numbered 0, 1, ..., len() - 1.
+// Solvers only see cities through this trait so they can run on coordinates,
+// precomputed matrices or any cost function.
+pub trait Metric {
+    // Number of cities
+    fn len(&self) -> usize;
+
+    fn dist(&self, i: usize, j: usize) -> Cost;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Whether every distance is the same in both directions, which takes O(n^2) time
+    fn is_symmetric(&self) -> bool {
+        (0..self.len()).all(|i| (0..i).all(|j| self.dist(i, j) == self.dist(j, i)))
+    }
+
+    // Length of the cycle visiting cities in the order of cities_idx
+    // and going back to the first city
+    fn tour_length(&self, cities_idx: &[usize]) -> Cost {
+        let n = cities_idx.len();
+        (0..n).fold(0, |sum, i| {
+            sum + self.dist(cities_idx[i], cities_idx[(i + 1) % n])
+        })
+    }
+}
+
+// Metric computing distances from coordinates each time they are needed
+#[derive(Debug, Clone)]
+pub struct CoordMetric {
+    coords: Vec<(f64, f64)>,
+    edge_weight_type: EdgeWeightType,
+}
+
+impl CoordMetric {
+    pub fn new(coords: Vec<(f64, f64)>, edge_weight_type: EdgeWeightType) -> CoordMetric {
+        assert!(
+            edge_weight_type != EdgeWeightType::Explicit && !edge_weight_type.is_3d(),
+            "CoordMetric needs a 2D EDGE_WEIGHT_TYPE"
+        );
+        CoordMetric {
+            coords,
+            edge_weight_type,
+        }
+    }
+
+    pub fn coords(&self) -> &[(f64, f64)] {
+        &self.coords
+    }
+}
+
+impl Metric for CoordMetric {
+    fn len(&self) -> usize {
+        self.coords.len()
+    }
+
+    fn dist(&self, i: usize, j: usize) -> Cost {
+        let (a, b) = (self.coords[i], self.coords[j]);
+        self.edge_weight_type
+            .distance((a.0, a.1, 0.), (b.0, b.1, 0.))
+    }
+}
+
+// Metric given by a function such as a custom cost of road networks
+pub struct FnMetric<F> {
+    len: usize,
+    f: F,
+}
+
+impl<F: Fn(usize, usize) -> Cost> FnMetric<F> {
+    pub fn new(len: usize, f: F) -> FnMetric<F> {
+        FnMetric { len, f }
+    }
+}
+
+impl<F: Fn(usize, usize) -> Cost> Metric for FnMetric<F> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn dist(&self, i: usize, j: usize) -> Cost {
+        (self.f)(i, j)
+    }
+}
+
+// Distance functions named by EDGE_WEIGHT_TYPE of TSPLIB.
+// Rounding follows the TSPLIB 95 documentation so that tour lengths match published optima.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeWeightType {
+    // Distances are given in EDGE_WEIGHT_SECTION
+    Explicit,
+    #[default]
+    Euc2d,
+    Euc3d,
+    Max2d,
+    Max3d,
+    Man2d,
+    Man3d,
+    Ceil2d,
+    // Great circle distance on latitude and longitude written in DDD.MM form
+    Geo,
+    // Pseudo-Euclidean distance used by att48 and att532
+    Att,
+}
+
+impl FromStr for EdgeWeightType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<EdgeWeightType, String> {
+        match s {
+            "EXPLICIT" => Ok(EdgeWeightType::Explicit),
+            "EUC_2D" => Ok(EdgeWeightType::Euc2d),
+            "EUC_3D" => Ok(EdgeWeightType::Euc3d),
+            "MAX_2D" => Ok(EdgeWeightType::Max2d),
+            "MAX_3D" => Ok(EdgeWeightType::Max3d),
+            "MAN_2D" => Ok(EdgeWeightType::Man2d),
+            "MAN_3D" => Ok(EdgeWeightType::Man3d),
+            "CEIL_2D" => Ok(EdgeWeightType::Ceil2d),
+            "GEO" => Ok(EdgeWeightType::Geo),
+            "ATT" => Ok(EdgeWeightType::Att),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+impl EdgeWeightType {
+    pub fn is_3d(&self) -> bool {
+        matches!(
+            self,
+            EdgeWeightType::Euc3d | EdgeWeightType::Max3d | EdgeWeightType::Man3d
+        )
+    }
+
+    // Distance between two points given as (x, y, z). z is ignored by 2D types.
+    pub fn distance(&self, a: (f64, f64, f64), b: (f64, f64, f64)) -> i32 {
+        let dx = (a.0 - b.0).abs();
+        let dy = (a.1 - b.1).abs();
+        let dz = (a.2 - b.2).abs();
+
+        match self {
+            EdgeWeightType::Explicit => {
+                panic!("EXPLICIT distances are not computed from coordinates")
+            }
+            EdgeWeightType::Euc2d => nint((dx * dx + dy * dy).sqrt()),
+            EdgeWeightType::Euc3d => nint((dx * dx + dy * dy + dz * dz).sqrt()),
+            EdgeWeightType::Max2d => nint(dx).max(nint(dy)),
+            EdgeWeightType::Max3d => nint(dx).max(nint(dy)).max(nint(dz)),
+            EdgeWeightType::Man2d => nint(dx + dy),
+            EdgeWeightType::Man3d => nint(dx + dy + dz),
+            EdgeWeightType::Ceil2d => (dx * dx + dy * dy).sqrt().ceil() as i32,
+            EdgeWeightType::Geo => geo(a, b),
+            EdgeWeightType::Att => {
+                let r = ((dx * dx + dy * dy) / 10.0).sqrt();
+                let t = nint(r);
+                if (t as f64) < r {
+                    t + 1
+                } else {
+                    t
+                }
+            }
+        }
+    }
+}
+
+fn nint(x: f64) -> i32 {
+    (x + 0.5) as i32
+}
+
+// Convert DDD.MM form to radian. The degree part is truncated as in
+// the TSPLIB FAQ, which is what published optima are computed with.
+fn geo_radian(x: f64) -> f64 {
+    // TSPLIB uses this rounded value, not std::f64::consts::PI
+    #[allow(clippy::approx_constant)]
+    const PI: f64 = 3.141592;
+    let deg = x.trunc();
+    let min = x - deg;
+    PI * (deg + 5.0 * min / 3.0) / 180.0
+}
+
+fn geo(a: (f64, f64, f64), b: (f64, f64, f64)) -> i32 {
+    // Radius of the earth in km
+    const RRR: f64 = 6378.388;
+    let (lat_a, lon_a) = (geo_radian(a.0), geo_radian(a.1));
+    let (lat_b, lon_b) = (geo_radian(b.0), geo_radian(b.1));
+
+    let q1 = (lon_a - lon_b).cos();
+    let q2 = (lat_a - lat_b).cos();
+    let q3 = (lat_a + lat_b).cos();
+    (RRR * (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).acos() + 1.0) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics() {
+        let coords = vec![(0., 0.), (3., 0.), (3., 4.)];
+        let m = CoordMetric::new(coords.clone(), EdgeWeightType::Euc2d);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.dist(0, 2), 5);
+        assert_eq!(m.tour_length(&[0, 1, 2]), 3 + 4 + 5);
+        assert_eq!(m.coords(), &coords[..]);
+
+        let m = FnMetric::new(4, |i, j| (i as Cost - j as Cost).abs());
+        assert_eq!(m.len(), 4);
+        assert_eq!(m.dist(3, 1), 2);
+        assert_eq!(m.tour_length(&[0, 1, 2, 3]), 6);
+        assert_eq!(m.tour_length(&[0, 2, 1, 3]), 2 + 1 + 2 + 3);
+        assert!(m.is_symmetric());
+
+        let m = FnMetric::new(3, |i, j| if (i + 1) % 3 == j { 1 } else { 2 });
+        assert!(!m.is_symmetric());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("EUC_2D".parse(), Ok(EdgeWeightType::Euc2d));
+        assert_eq!("GEO".parse(), Ok(EdgeWeightType::Geo));
+        assert_eq!("XRAY1".parse::<EdgeWeightType>(), Err("XRAY1".to_string()));
+        assert!(EdgeWeightType::Man3d.is_3d());
+        assert!(!EdgeWeightType::Ceil2d.is_3d());
+    }
+
+    #[test]
+    fn euclidean() {
+        let a = (0., 0., 0.);
+        // 1.5 is rounded up while it used to be truncated
+        assert_eq!(EdgeWeightType::Euc2d.distance(a, (1.5, 0., 0.)), 2);
+        assert_eq!(EdgeWeightType::Euc2d.distance(a, (1.4, 0., 0.)), 1);
+        assert_eq!(EdgeWeightType::Euc2d.distance(a, (3., 4., 100.)), 5);
+        assert_eq!(EdgeWeightType::Euc3d.distance(a, (2., 3., 6.)), 7);
+        assert_eq!(EdgeWeightType::Ceil2d.distance(a, (1., 1., 0.)), 2);
+        assert_eq!(EdgeWeightType::Ceil2d.distance(a, (3., 4., 0.)), 5);
+    }
+
+    #[test]
+    fn manhattan_and_maximum() {
+        let a = (1., 2., 3.);
+        let b = (4., -2.4, 4.);
+        assert_eq!(EdgeWeightType::Man2d.distance(a, b), 7);
+        assert_eq!(EdgeWeightType::Man3d.distance(a, b), 8);
+        assert_eq!(EdgeWeightType::Max2d.distance(a, b), 4);
+        assert_eq!(
+            EdgeWeightType::Max3d.distance((0., 0., 0.), (1., 2., 9.)),
+            9
+        );
+    }
+
+    #[test]
+    fn att() {
+        // First two cities of att48
+        let a = (6734., 1453., 0.);
+        let b = (2233., 10., 0.);
+        assert_eq!(EdgeWeightType::Att.distance(a, b), 1495);
+        // sqrt((30^2 + 10^2) / 10) = 10 exactly so it is not rounded up
+        assert_eq!(
+            EdgeWeightType::Att.distance((0., 0., 0.), (30., 10., 0.)),
+            10
+        );
+        // sqrt((31^2 + 10^2) / 10) = 10.30.. is rounded up
+        assert_eq!(
+            EdgeWeightType::Att.distance((0., 0., 0.), (31., 10., 0.)),
+            11
+        );
+    }
+
+    #[test]
+    fn geo() {
+        // 38.30 is 38 degrees 30 minutes, i.e. 38.5 degrees
+        assert!((geo_radian(38.30) / geo_radian(1.0) - 38.5).abs() < 1e-9);
+        assert_eq!(EdgeWeightType::Geo.distance((0., 0., 0.), (0., 0., 0.)), 1);
+        // One degree of longitude on the equator is about 111.3 km
+        assert_eq!(
+            EdgeWeightType::Geo.distance((0., 0., 0.), (0., 1., 0.)),
+            112
+        );
+        // Symmetric
+        let a = (38.24, 20.42, 0.);
+        let b = (39.57, 26.15, 0.);
+        assert_eq!(
+            EdgeWeightType::Geo.distance(a, b),
+            EdgeWeightType::Geo.distance(b, a)
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::metric::Metric;
+use std::borrow::Cow;
+
+// K nearest cities of each city sorted by distance.
+// Local searches only try moves adding edges to these candidates.
+// Whether the metric is symmetric is kept too, so searches don't check it every time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeighborLists {
+    lists: Vec<Vec<usize>>,
+    symmetric: bool,
+}
+
+impl NeighborLists {
+    pub fn new<M: Metric + ?Sized>(metric: &M, k: usize) -> NeighborLists {
+        let n = metric.len();
+        let k = k.min(n.saturating_sub(1));
+
+        let lists = (0..n)
+            .map(|i| {
+                let mut cities = (0..n).filter(|j| *j != i).collect::<Vec<usize>>();
+                // Ties are broken by index so the lists are deterministic
+                cities.sort_by_key(|j| (metric.dist(i, *j), *j));
+                cities.truncate(k);
+                cities
+            })
+            .collect();
+
+        NeighborLists {
+            lists,
+            symmetric: metric.is_symmetric(),
+        }
+    }
+
+    // Lists kept by LocalSearch::prepare if any, or new ones
+    pub fn prepared_or_new<'a, M: Metric + ?Sized>(
+        prepared: &'a Option<NeighborLists>,
+        metric: &M,
+        k: usize,
+    ) -> Cow<'a, NeighborLists> {
+        match prepared {
+            Some(lists) => Cow::Borrowed(lists),
+            None => Cow::Owned(NeighborLists::new(metric, k)),
+        }
+    }
+
+    pub fn get(&self, i: usize) -> &[usize] {
+        &self.lists[i]
+    }
+
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+
+    pub fn len(&self) -> usize {
+        self.lists.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lists.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metric::{Cost, FnMetric};
+
+    #[test]
+    fn neighbors() {
+        // Cities on a line at 0, 1, 3, 7
+        let x = [0, 1, 3, 7];
+        let metric = FnMetric::new(4, |i, j| (x[i] as Cost - x[j] as Cost).abs());
+
+        let lists = NeighborLists::new(&metric, 2);
+        assert_eq!(lists.len(), 4);
+        assert_eq!(lists.get(0), &[1, 2]);
+        assert_eq!(lists.get(2), &[1, 0]);
+        assert_eq!(lists.get(3), &[2, 1]);
+
+        // K is limited by the number of other cities
+        let lists = NeighborLists::new(&metric, 10);
+        assert_eq!(lists.get(1), &[0, 2, 3]);
+    }
+}
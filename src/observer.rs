@@ -0,0 +1,184 @@
+use crate::metric::Cost;
+
+// Progress reported by solvers while they run.
+// New solvers may add variants, so matches need a wildcard arm outside this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event<'a> {
+    // Edge connecting two cities was added by a construction heuristic
+    EdgeAdded(usize, usize),
+    // City was added to the partial path cities_idx
+    CityInserted {
+        city: usize,
+        cities_idx: &'a [usize],
+    },
+    // 2-opt reversed cities_idx[i..=j] and cities_idx is the tour after the move
+    TwoOptMove {
+        i: usize,
+        j: usize,
+        cities_idx: &'a [usize],
+    },
+    // Or-opt moved segment, possibly reversed, and cities_idx is the tour after the move
+    OrOptMove {
+        segment: &'a [usize],
+        reversed: bool,
+        cities_idx: &'a [usize],
+    },
+    // 3-opt replaced three edges and cities_idx is the tour after the move
+    ThreeOptMove {
+        reconnection: Reconnection,
+        cities_idx: &'a [usize],
+    },
+    // Lin-Kernighan exchanged the number of edges and cities_idx is the tour after the move
+    LinKernighanMove {
+        edges: usize,
+        cities_idx: &'a [usize],
+    },
+    // Cities at positions i and j were exchanged and cities_idx is the tour after the move
+    SwapMove {
+        i: usize,
+        j: usize,
+        cities_idx: &'a [usize],
+    },
+    // Tabu search found a tour shorter than any before after the number of moves.
+    // The events give the convergence trace starting from the given tour at 0 moves.
+    TabuBest {
+        iteration: usize,
+        cost: Cost,
+    },
+    // Exact solver bounded the optimal length from below and cost is the shortest tour found.
+    // They are equal when the tour is optimal.
+    LowerBound {
+        lower_bound: Cost,
+        cost: Cost,
+    },
+    // Shortest tour found so far
+    NewBest {
+        cities_idx: &'a [usize],
+        cost: Cost,
+    },
+}
+
+// Ways to reconnect the tour a b..c d..e f after removing edges (a, b), (c, d) and (e, f),
+// reported by Event::ThreeOptMove. The first three are 2-opt moves since one of the removed
+// edges is added again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconnection {
+    // a c..b d..e f
+    ReverseFirst,
+    // a b..c e..d f
+    ReverseSecond,
+    // a e..d c..b f
+    ReverseBoth,
+    // a c..b e..d f
+    ReverseEach,
+    // a d..e b..c f, which moves a segment without reversing any part of the tour
+    Exchange,
+    // a d..e c..b f
+    ExchangeReverseFirst,
+    // a e..d b..c f
+    ExchangeReverseSecond,
+}
+
+impl Reconnection {
+    pub const ALL: [Reconnection; 7] = [
+        Reconnection::ReverseFirst,
+        Reconnection::ReverseSecond,
+        Reconnection::ReverseBoth,
+        Reconnection::ReverseEach,
+        Reconnection::Exchange,
+        Reconnection::ExchangeReverseFirst,
+        Reconnection::ExchangeReverseSecond,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Reconnection::ReverseFirst => "reverse_first",
+            Reconnection::ReverseSecond => "reverse_second",
+            Reconnection::ReverseBoth => "reverse_both",
+            Reconnection::ReverseEach => "reverse_each",
+            Reconnection::Exchange => "exchange",
+            Reconnection::ExchangeReverseFirst => "exchange_reverse_first",
+            Reconnection::ExchangeReverseSecond => "exchange_reverse_second",
+        }
+    }
+}
+
+// Receiver of events such as plotters, loggers or progress bars
+pub trait Observer {
+    fn notify(&mut self, event: &Event);
+}
+
+// Observer ignoring all events
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullObserver;
+
+impl Observer for NullObserver {
+    fn notify(&mut self, _: &Event) {}
+}
+
+// Any closure taking events is an observer
+impl<F: FnMut(&Event)> Observer for F {
+    fn notify(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::TSP_FILE_BERLIN52, greedy::Greedy, nearest_insertion::NearestInsertion,
+        solver::ConstructionHeuristic, tsplib::Instance, two_opt,
+    };
+
+    // Observer recording events instead of drawing them
+    #[derive(Default)]
+    struct Recorder {
+        edges: usize,
+        inserted: Vec<usize>,
+        moves: usize,
+    }
+
+    impl Observer for Recorder {
+        fn notify(&mut self, event: &Event) {
+            match event {
+                Event::EdgeAdded(..) => self.edges += 1,
+                Event::CityInserted { city, cities_idx } => {
+                    assert!(cities_idx.contains(city));
+                    self.inserted.push(*city);
+                }
+                Event::TwoOptMove { i, j, .. } => {
+                    assert!(i < j);
+                    self.moves += 1;
+                }
+                Event::OrOptMove { .. }
+                | Event::ThreeOptMove { .. }
+                | Event::LinKernighanMove { .. }
+                | Event::SwapMove { .. }
+                | Event::TabuBest { .. }
+                | Event::LowerBound { .. }
+                | Event::NewBest { .. } => {}
+            }
+        }
+    }
+
+    #[test]
+    fn events() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+
+        // Greedy adds all edges of the cycle
+        let mut recorder = Recorder::default();
+        let mut cities_idx = Greedy.construct(&instance, &mut recorder);
+        assert_eq!(recorder.edges, 52);
+
+        two_opt::solver(&mut recorder, &instance, &mut cities_idx, 0);
+        assert!(recorder.moves > 0);
+
+        // Nearest insertion inserts all cities but the first three
+        let mut recorder = Recorder::default();
+        NearestInsertion.construct(&instance, &mut recorder);
+        recorder.inserted.sort();
+        assert_eq!(recorder.inserted, (3..52).collect::<Vec<usize>>());
+    }
+}
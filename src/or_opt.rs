@@ -0,0 +1,395 @@
+use rand::rngs::StdRng;
+
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+use crate::observer::{Event, Observer};
+use crate::solver::LocalSearch;
+use crate::two_opt::NEIGHBORS;
+use std::time::Instant;
+
+// Longest segment moved by default
+pub const MAX_SEGMENT: usize = 3;
+
+// Local search moving segments of 1 to max_segment consecutive cities to another place,
+// optionally reversed. Like TwoOpt, moves next to the nearest neighbors are tried first
+// and all places are checked at the end, so no improving move remains when it returns
+// unless deadline has passed.
+#[derive(Debug, Clone)]
+pub struct OrOpt {
+    max_segment: usize,
+    neighbors: usize,
+    reverse: bool,
+    // Neighbor lists kept between prepare and finish
+    prepared: Option<NeighborLists>,
+}
+
+impl Default for OrOpt {
+    fn default() -> OrOpt {
+        OrOpt {
+            max_segment: MAX_SEGMENT,
+            neighbors: NEIGHBORS,
+            reverse: true,
+            prepared: None,
+        }
+    }
+}
+
+impl OrOpt {
+    pub fn new() -> OrOpt {
+        OrOpt::default()
+    }
+
+    pub fn max_segment(mut self, len: usize) -> OrOpt {
+        self.max_segment = len;
+        self
+    }
+
+    // Number of nearest neighbors of segment ends tried as new edges
+    pub fn neighbors(mut self, k: usize) -> OrOpt {
+        self.neighbors = k;
+        self
+    }
+
+    // Whether segments may be inserted in the reversed order
+    pub fn reverse(mut self, reverse: bool) -> OrOpt {
+        self.reverse = reverse;
+        self
+    }
+}
+
+impl LocalSearch for OrOpt {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        _: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        let neighbors = NeighborLists::prepared_or_new(&self.prepared, metric, self.neighbors);
+        local_optimum(
+            observer,
+            metric,
+            cities_idx,
+            &neighbors,
+            self.max_segment,
+            self.reverse,
+            deadline,
+        );
+    }
+
+    fn prepare(&mut self, metric: &dyn Metric) {
+        self.prepared = Some(NeighborLists::new(metric, self.neighbors));
+    }
+
+    fn finish(&mut self) {
+        self.prepared = None;
+    }
+}
+
+// Move of the segment of len cities starting at position start
+// between city after and its successor
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    start: usize,
+    len: usize,
+    after: usize,
+    reversed: bool,
+}
+
+// Apply improving moves until none remains.
+// Segments are never reversed on asymmetric metrics since that changes the length of
+// the edges inside them, while moving them in the same direction is still exact.
+pub fn local_optimum<M: Metric + ?Sized>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    neighbors: &NeighborLists,
+    max_segment: usize,
+    reverse: bool,
+    deadline: Option<Instant>,
+) {
+    let reverse = reverse && neighbors.is_symmetric();
+    let city_len = cities_idx.len();
+    let mut pos = vec![0; city_len];
+    // Position from which the next search starts
+    let mut start = 0;
+
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return;
+        }
+
+        for (i, city) in cities_idx.iter().enumerate() {
+            pos[*city] = i;
+        }
+
+        let search = Search {
+            metric,
+            cities_idx,
+            pos: &pos,
+            max_segment,
+            reverse,
+        };
+        let found = match search.run(start, Some(neighbors)) {
+            Some(m) => Some(m),
+            None => search.run(0, None),
+        };
+
+        match found {
+            Some(m) => {
+                start = m.start;
+                apply(observer, cities_idx, m);
+            }
+            // Local optimum
+            None => return,
+        }
+    }
+}
+
+struct Search<'a, M: ?Sized> {
+    metric: &'a M,
+    cities_idx: &'a [usize],
+    pos: &'a [usize],
+    max_segment: usize,
+    reverse: bool,
+}
+
+impl<M: Metric + ?Sized> Search<'_, M> {
+    fn succ(&self, city: usize) -> usize {
+        self.cities_idx[(self.pos[city] + 1) % self.cities_idx.len()]
+    }
+
+    fn pred(&self, city: usize) -> usize {
+        let city_len = self.cities_idx.len();
+        self.cities_idx[(self.pos[city] + city_len - 1) % city_len]
+    }
+
+    // Find the first improving move of segments starting at start or later.
+    // Only edges next to the neighbors of segment ends are tried if neighbors is given,
+    // otherwise all edges are tried.
+    fn run(&self, start: usize, neighbors: Option<&NeighborLists>) -> Option<Move> {
+        let city_len = self.cities_idx.len();
+
+        for step in 0..city_len {
+            let i = (start + step) % city_len;
+
+            // At least three other cities are needed to move a segment somewhere else
+            for len in 1..=self.max_segment.min(city_len.saturating_sub(3)) {
+                let s1 = self.cities_idx[i];
+                let s2 = self.cities_idx[(i + len - 1) % city_len];
+                let in_segment = |city: usize| (self.pos[city] + city_len - i) % city_len < len;
+
+                let p = self.pred(s1);
+                let n = self.succ(s2);
+                // Gain of removing the segment and connecting p and n
+                let gain =
+                    self.metric.dist(p, s1) + self.metric.dist(s2, n) - self.metric.dist(p, n);
+
+                let try_edge = |c: usize, sc: usize| {
+                    if in_segment(c) || in_segment(sc) {
+                        return None;
+                    }
+                    self.insertion(gain, i, len, (s1, s2), (c, sc))
+                };
+
+                let found =
+                    match neighbors {
+                        Some(neighbors) => neighbors
+                            .get(s1)
+                            .iter()
+                            .chain(neighbors.get(s2))
+                            .find_map(|c| {
+                                try_edge(*c, self.succ(*c)).or_else(|| try_edge(self.pred(*c), *c))
+                            }),
+                        None => self
+                            .cities_idx
+                            .iter()
+                            .find_map(|c| try_edge(*c, self.succ(*c))),
+                    };
+                if found.is_some() {
+                    return found;
+                }
+            }
+        }
+
+        None
+    }
+
+    // Improving move inserting segment s1..s2 between c and sc
+    fn insertion(
+        &self,
+        gain: Cost,
+        start: usize,
+        len: usize,
+        (s1, s2): (usize, usize),
+        (c, sc): (usize, usize),
+    ) -> Option<Move> {
+        let d = |a: usize, b: usize| self.metric.dist(a, b);
+        let base = d(c, sc) + gain;
+
+        let forward = d(c, s1) + d(s2, sc) - base;
+        let backward = d(c, s2) + d(s1, sc) - base;
+
+        let (delta, reversed) = if self.reverse && backward < forward {
+            (backward, true)
+        } else {
+            (forward, false)
+        };
+
+        if delta < 0 {
+            Some(Move {
+                start,
+                len,
+                after: c,
+                reversed,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn apply(observer: &mut dyn Observer, cities_idx: &mut [usize], m: Move) {
+    let city_len = cities_idx.len();
+
+    let mut segment = (0..m.len)
+        .map(|k| cities_idx[(m.start + k) % city_len])
+        .collect::<Vec<usize>>();
+    let mut rest = cities_idx
+        .iter()
+        .filter(|city| !segment.contains(city))
+        .copied()
+        .collect::<Vec<usize>>();
+
+    if m.reversed {
+        segment.reverse();
+    }
+    let at = rest.iter().position(|city| *city == m.after).unwrap() + 1;
+    rest.splice(at..at, segment.iter().copied());
+    cities_idx.copy_from_slice(&rest);
+
+    observer.notify(&Event::OrOptMove {
+        segment: &segment,
+        reversed: m.reversed,
+        cities_idx,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225},
+        greedy::Greedy,
+        metric::FnMetric,
+        observer::NullObserver,
+        solver::{ConstructionHeuristic, Pipeline, Solver},
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+    use rand::SeedableRng;
+
+    fn is_local_optimum<M: Metric + ?Sized>(
+        metric: &M,
+        cities_idx: &[usize],
+        reverse: bool,
+    ) -> bool {
+        let mut pos = vec![0; cities_idx.len()];
+        for (i, city) in cities_idx.iter().enumerate() {
+            pos[*city] = i;
+        }
+        let search = Search {
+            metric,
+            cities_idx,
+            pos: &pos,
+            max_segment: MAX_SEGMENT,
+            reverse,
+        };
+        search.run(0, None).is_none()
+    }
+
+    // Or-opt after 2-opt removes the slack left by 2-opt
+    #[test]
+    fn after_two_opt() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+            let or_opt = Pipeline::new(Greedy)
+                .then(TwoOpt::new())
+                .then(OrOpt::new())
+                .solve(&instance);
+
+            assert!(or_opt.cost <= two_opt.cost);
+            assert!(is_local_optimum(&instance, &or_opt.cities, true));
+
+            let mut sorted = or_opt.cities.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..instance.len()).collect::<Vec<usize>>());
+        }
+    }
+
+    #[test]
+    fn segments() {
+        // Cities on a line. Visiting 1 2 between 0 and 3 is the only shortest tour
+        // up to rotation and direction.
+        let x = [0, 1, 2, 3, 4, 5];
+        let metric = FnMetric::new(6, |i, j| (x[i] as Cost - x[j] as Cost).abs());
+        let neighbors = NeighborLists::new(&metric, 2);
+
+        for (initial, reverse) in [
+            // Single city
+            (vec![0, 2, 3, 1, 4, 5], false),
+            // Segment of two cities inserted as it is
+            (vec![0, 3, 1, 2, 4, 5], false),
+            // Reversed segment
+            (vec![0, 3, 2, 1, 4, 5], true),
+        ] {
+            let mut cities_idx = initial.clone();
+            local_optimum(
+                &mut NullObserver,
+                &metric,
+                &mut cities_idx,
+                &neighbors,
+                MAX_SEGMENT,
+                reverse,
+                None,
+            );
+            assert_eq!(metric.tour_length(&cities_idx), 10, "{:?}", initial);
+        }
+    }
+
+    #[test]
+    fn improve() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let mut cities_idx = (0..instance.len()).collect::<Vec<usize>>();
+        let initial = instance.tour_length(&cities_idx);
+
+        OrOpt::new().max_segment(1).reverse(false).improve(
+            &instance,
+            &mut cities_idx,
+            None,
+            &mut StdRng::seed_from_u64(0),
+            &mut NullObserver,
+        );
+        assert!(instance.tour_length(&cities_idx) < initial);
+    }
+
+    // Only segments kept in the same direction are moved on asymmetric distances
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let initial = Greedy.construct(&metric, &mut NullObserver);
+
+        let mut cities_idx = initial.clone();
+        OrOpt::new().improve(
+            &metric,
+            &mut cities_idx,
+            None,
+            &mut StdRng::seed_from_u64(0),
+            &mut NullObserver,
+        );
+        assert!(metric.tour_length(&cities_idx) < metric.tour_length(&initial));
+        assert!(is_local_optimum(&metric, &cities_idx, false));
+    }
+}
@@ -0,0 +1,550 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+use crate::observer::{Event, Observer};
+use crate::solver::LocalSearch;
+use crate::two_opt::NEIGHBORS;
+use std::time::Instant;
+
+// Number of moves tried by default
+pub const ITERATIONS: usize = 10_000_000;
+// Temperature below which no worsening move is practically accepted
+pub const MIN_TEMPERATURE: f64 = 0.01;
+// Probability of accepting an average worsening move at the automatic initial temperature
+pub const INITIAL_ACCEPTANCE: f64 = 0.1;
+// Number of moves sampled to calibrate the initial temperature
+const SAMPLES: usize = 1000;
+// Longest segment moved by Or-opt moves
+const MAX_SEGMENT: usize = 3;
+
+// How the temperature falls as moves are tried
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cooling {
+    // Multiply the temperature by the rate after each move
+    Geometric(f64),
+    // Lower the temperature by the same amount after each move to reach 0 at the last one
+    Linear,
+    // Geometric cooling which reheats after stall moves without a new best tour.
+    // Each reheat starts from reheat times the temperature the last cooling started from.
+    // It runs until iterations or the deadline.
+    Adaptive {
+        rate: f64,
+        stall: usize,
+        reheat: f64,
+    },
+}
+
+impl Default for Cooling {
+    fn default() -> Cooling {
+        // Falls to 1 / 22000 of the initial temperature in ITERATIONS moves
+        Cooling::Geometric(0.999999)
+    }
+}
+
+// Simulated annealing with random 2-opt and Or-opt moves. Unlike two_opt::solver,
+// worsening moves are accepted with probability exp(-delta / temperature), which lets
+// the tour leave local optima while the temperature is high. The shortest tour seen is
+// returned. It stops after iterations moves, when the temperature falls below
+// min_temperature or when the deadline has passed. Asymmetric metrics only get Or-opt
+// moves since 2-opt deltas assume reversed parts of the tour keep their length.
+#[derive(Debug, Clone)]
+pub struct SimulatedAnnealing {
+    cooling: Cooling,
+    initial_temperature: Option<f64>,
+    min_temperature: f64,
+    iterations: usize,
+    or_opt: bool,
+    neighbors: usize,
+}
+
+impl Default for SimulatedAnnealing {
+    fn default() -> SimulatedAnnealing {
+        SimulatedAnnealing {
+            cooling: Cooling::default(),
+            initial_temperature: None,
+            min_temperature: MIN_TEMPERATURE,
+            iterations: ITERATIONS,
+            or_opt: true,
+            neighbors: NEIGHBORS,
+        }
+    }
+}
+
+impl SimulatedAnnealing {
+    pub fn new() -> SimulatedAnnealing {
+        SimulatedAnnealing::default()
+    }
+
+    pub fn cooling(mut self, cooling: Cooling) -> SimulatedAnnealing {
+        self.cooling = cooling;
+        self
+    }
+
+    // Without it the temperature is calibrated from sampled moves of the initial tour
+    // so that an average worsening move is accepted with INITIAL_ACCEPTANCE probability.
+    pub fn initial_temperature(mut self, temperature: f64) -> SimulatedAnnealing {
+        self.initial_temperature = Some(temperature);
+        self
+    }
+
+    pub fn min_temperature(mut self, temperature: f64) -> SimulatedAnnealing {
+        self.min_temperature = temperature;
+        self
+    }
+
+    pub fn iterations(mut self, iterations: usize) -> SimulatedAnnealing {
+        self.iterations = iterations;
+        self
+    }
+
+    // Whether Or-opt moves are tried besides 2-opt moves
+    pub fn or_opt(mut self, or_opt: bool) -> SimulatedAnnealing {
+        self.or_opt = or_opt;
+        self
+    }
+
+    // Number of nearest neighbors of each city which moves add edges to
+    pub fn neighbors(mut self, k: usize) -> SimulatedAnnealing {
+        self.neighbors = k;
+        self
+    }
+
+    // Anneal cities_idx and leave the shortest tour found in it.
+    // Returns the number of moves tried.
+    pub fn anneal<M: Metric + ?Sized, R: Rng>(
+        &self,
+        observer: &mut dyn Observer,
+        metric: &M,
+        cities_idx: &mut [usize],
+        rng: &mut R,
+        deadline: Option<Instant>,
+    ) -> usize {
+        let city_len = cities_idx.len();
+        let two_opt = metric.is_symmetric();
+        if city_len < 5 || !(two_opt || self.or_opt) {
+            return 0;
+        }
+
+        let neighbors = NeighborLists::new(metric, self.neighbors);
+        let mut pos = vec![0; city_len];
+        for (i, city) in cities_idx.iter().enumerate() {
+            pos[*city] = i;
+        }
+
+        let initial = self.initial_temperature.unwrap_or_else(|| {
+            calibrate(
+                metric,
+                cities_idx,
+                &pos,
+                &neighbors,
+                rng,
+                two_opt,
+                self.or_opt,
+            )
+        });
+        let mut temperature = initial;
+
+        let mut cost = metric.tour_length(cities_idx);
+        let mut best = cities_idx.to_vec();
+        let mut best_cost = cost;
+        // Temperature the current cooling started from
+        let mut start = temperature;
+        let mut since_best = 0;
+
+        let mut tried = 0;
+        for k in 0..self.iterations {
+            // Reading the clock every iteration is slow
+            if k % 1024 == 0 && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
+            }
+            if temperature < self.min_temperature
+                && !matches!(self.cooling, Cooling::Adaptive { .. })
+            {
+                break;
+            }
+
+            tried += 1;
+            let m = Move::random(rng, cities_idx, &pos, &neighbors, two_opt, self.or_opt);
+            let delta = m.delta(metric, cities_idx);
+            if delta <= 0 || rng.gen::<f64>() < (-delta as f64 / temperature).exp() {
+                m.apply(observer, cities_idx, &mut pos);
+                cost += delta;
+                if cost < best_cost {
+                    best_cost = cost;
+                    best.copy_from_slice(cities_idx);
+                    since_best = 0;
+                }
+            }
+            since_best += 1;
+
+            temperature = match self.cooling {
+                Cooling::Geometric(rate) => temperature * rate,
+                Cooling::Linear => {
+                    initial * (self.iterations - k - 1) as f64 / self.iterations as f64
+                }
+                Cooling::Adaptive {
+                    rate,
+                    stall,
+                    reheat,
+                } => {
+                    if since_best >= stall {
+                        since_best = 0;
+                        start *= reheat;
+                        start
+                    } else {
+                        temperature * rate
+                    }
+                }
+            };
+        }
+
+        cities_idx.copy_from_slice(&best);
+        tried
+    }
+}
+
+impl LocalSearch for SimulatedAnnealing {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        rng: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        self.anneal(observer, metric, cities_idx, rng, deadline);
+    }
+}
+
+// Temperature accepting the average worsening move of the tour with INITIAL_ACCEPTANCE
+fn calibrate<M: Metric + ?Sized, R: Rng>(
+    metric: &M,
+    cities_idx: &[usize],
+    pos: &[usize],
+    neighbors: &NeighborLists,
+    rng: &mut R,
+    two_opt: bool,
+    or_opt: bool,
+) -> f64 {
+    let (sum, count) = (0..SAMPLES)
+        .map(|_| {
+            Move::random(rng, cities_idx, pos, neighbors, two_opt, or_opt).delta(metric, cities_idx)
+        })
+        .filter(|delta| *delta > 0)
+        .fold((0., 0), |(sum, count), delta| {
+            (sum + delta as f64, count + 1)
+        });
+
+    if count == 0 {
+        return 1.;
+    }
+    -(sum / count as f64) / INITIAL_ACCEPTANCE.ln()
+}
+
+// Random move of the tour. Segments of Or-opt moves do not wrap around the end.
+#[derive(Debug, Clone, Copy)]
+enum Move {
+    // Reverse cities_idx[i..=j]
+    TwoOpt { i: usize, j: usize },
+    // Move cities_idx[i..i + len] between cities_idx[j] and its successor
+    OrOpt { i: usize, len: usize, j: usize },
+}
+
+impl Move {
+    // Move adding an edge between a random city and one of its nearest neighbors.
+    // At least one of two_opt and or_opt must be set.
+    fn random<R: Rng>(
+        rng: &mut R,
+        cities_idx: &[usize],
+        pos: &[usize],
+        neighbors: &NeighborLists,
+        two_opt: bool,
+        or_opt: bool,
+    ) -> Move {
+        let n = cities_idx.len();
+        loop {
+            if or_opt && (!two_opt || rng.gen::<bool>()) {
+                let len = rng.gen_range(1..=MAX_SEGMENT.min(n - 3));
+                let i = rng.gen_range(0..=n - len);
+                // Insert next to a neighbor of the first city of the segment
+                let list = neighbors.get(cities_idx[i]);
+                let c = list[rng.gen_range(0..list.len())];
+                let j = if rng.gen::<bool>() {
+                    pos[c]
+                } else {
+                    (pos[c] + n - 1) % n
+                };
+                // Edges touching the segment are not places to insert it
+                if (i..i + len).contains(&j) || j == (i + n - 1) % n {
+                    continue;
+                }
+                return Move::OrOpt { i, len, j };
+            } else {
+                // Adding (a, c) and either (succ a, succ c) or (pred a, pred c)
+                let a = rng.gen_range(0..n);
+                let list = neighbors.get(a);
+                let c = list[rng.gen_range(0..list.len())];
+                let (p, q) = (pos[a].min(pos[c]), pos[a].max(pos[c]));
+                let (i, j) = if rng.gen::<bool>() {
+                    (p + 1, q)
+                } else {
+                    (p, q.saturating_sub(1))
+                };
+                // Reversing the whole tour or all but one city does not change it
+                if i >= j || j - i + 2 >= n {
+                    continue;
+                }
+                return Move::TwoOpt { i, j };
+            }
+        }
+    }
+
+    fn delta<M: Metric + ?Sized>(&self, metric: &M, cities_idx: &[usize]) -> Cost {
+        let n = cities_idx.len();
+        let at = |p: usize| cities_idx[p % n];
+        let d = |a: usize, b: usize| metric.dist(a, b);
+
+        match *self {
+            Move::TwoOpt { i, j } => {
+                let (x, y) = (at(i + n - 1), at(j + 1));
+                d(x, at(j)) + d(at(i), y) - d(x, at(i)) - d(at(j), y)
+            }
+            Move::OrOpt { i, len, j } => {
+                let (s1, s2) = (at(i), at(i + len - 1));
+                let (p, nx) = (at(i + n - 1), at(i + len));
+                let (c, sc) = (at(j), at(j + 1));
+                d(p, nx) + d(c, s1) + d(s2, sc) - d(p, s1) - d(s2, nx) - d(c, sc)
+            }
+        }
+    }
+
+    fn apply(&self, observer: &mut dyn Observer, cities_idx: &mut [usize], pos: &mut [usize]) {
+        match *self {
+            Move::TwoOpt { i, j } => {
+                cities_idx[i..=j].reverse();
+                for p in i..=j {
+                    pos[cities_idx[p]] = p;
+                }
+                observer.notify(&Event::TwoOptMove { i, j, cities_idx });
+            }
+            Move::OrOpt { i, len, j } => {
+                let start = if j > i {
+                    cities_idx[i..=j].rotate_left(len);
+                    j + 1 - len
+                } else {
+                    cities_idx[j + 1..i + len].rotate_right(len);
+                    j + 1
+                };
+                for p in i.min(j + 1)..=j.max(i + len - 1) {
+                    pos[cities_idx[p]] = p;
+                }
+                let segment = cities_idx[start..start + len].to_vec();
+                observer.notify(&Event::OrOptMove {
+                    segment: &segment,
+                    reversed: false,
+                    cities_idx,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100},
+        greedy::Greedy,
+        observer::NullObserver,
+        solver::{ConstructionHeuristic, Pipeline, Solver},
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    // Delta of random moves is the change of the tour length
+    #[test]
+    fn moves() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let mut cities_idx = (0..instance.len()).collect::<Vec<usize>>();
+        let mut pos = cities_idx.clone();
+
+        for _ in 0..10000 {
+            let m = Move::random(&mut rng, &cities_idx, &pos, &neighbors, true, true);
+            let cost = instance.tour_length(&cities_idx);
+            let delta = m.delta(&instance, &cities_idx);
+            m.apply(&mut NullObserver, &mut cities_idx, &mut pos);
+            assert_eq!(instance.tour_length(&cities_idx), cost + delta, "{:?}", m);
+            for (i, city) in cities_idx.iter().enumerate() {
+                assert_eq!(pos[*city], i);
+            }
+        }
+
+        let mut sorted = cities_idx.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..instance.len()).collect::<Vec<usize>>());
+
+        // Or-opt deltas hold for asymmetric distances too
+        let metric = random_asymmetric(30, 0);
+        let neighbors = NeighborLists::new(&metric, NEIGHBORS);
+        let mut cities_idx = (0..metric.len()).collect::<Vec<usize>>();
+        let mut pos = cities_idx.clone();
+        for _ in 0..10000 {
+            let m = Move::random(&mut rng, &cities_idx, &pos, &neighbors, false, true);
+            let cost = metric.tour_length(&cities_idx);
+            let delta = m.delta(&metric, &cities_idx);
+            m.apply(&mut NullObserver, &mut cities_idx, &mut pos);
+            assert_eq!(metric.tour_length(&cities_idx), cost + delta, "{:?}", m);
+        }
+    }
+
+    // Only Or-opt moves are made on asymmetric distances, whose deltas stay exact
+    #[test]
+    fn asymmetric() {
+        for seed in 0..5 {
+            let metric = random_asymmetric(30, seed);
+            let initial = Greedy.construct(&metric, &mut NullObserver);
+            let mut rng = StdRng::seed_from_u64(0);
+
+            let mut cities_idx = initial.clone();
+            let mut cost = metric.tour_length(&initial);
+            SimulatedAnnealing::new().iterations(100_000).anneal(
+                &mut |event: &Event| {
+                    assert!(matches!(event, Event::OrOptMove { .. }));
+                },
+                &metric,
+                &mut cities_idx,
+                &mut rng,
+                None,
+            );
+            assert!(metric.tour_length(&cities_idx) <= cost);
+
+            // Without Or-opt moves nothing is left to try
+            cost = metric.tour_length(&cities_idx);
+            let tried = SimulatedAnnealing::new().or_opt(false).anneal(
+                &mut NullObserver,
+                &metric,
+                &mut cities_idx,
+                &mut rng,
+                None,
+            );
+            assert_eq!(tried, 0);
+            assert_eq!(metric.tour_length(&cities_idx), cost);
+        }
+    }
+
+    // Annealing escapes the local optimum where 2-opt stops with any cooling schedule.
+    // On ts225 it depends on the seed so only the smaller instances are checked.
+    #[test]
+    fn cooling() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+
+            for cooling in [
+                Cooling::Geometric(0.99999),
+                Cooling::Linear,
+                Cooling::Adaptive {
+                    rate: 0.99999,
+                    stall: 100_000,
+                    reheat: 0.5,
+                },
+            ] {
+                let solution = Pipeline::new(Greedy)
+                    .then(
+                        SimulatedAnnealing::new()
+                            .iterations(1_000_000)
+                            .cooling(cooling),
+                    )
+                    .seed(0)
+                    .solve(&instance);
+                println!(
+                    "{}: 2-opt {} {:?} {}",
+                    tsp_file, two_opt.cost, cooling, solution.cost
+                );
+                assert!(solution.cost < two_opt.cost);
+                assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+            }
+        }
+    }
+
+    #[test]
+    fn temperature() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let initial = Pipeline::new(Greedy).solve(&instance);
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let mut pos = vec![0; instance.len()];
+        for (i, city) in initial.cities.iter().enumerate() {
+            pos[*city] = i;
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(
+            calibrate(
+                &instance,
+                &initial.cities,
+                &pos,
+                &neighbors,
+                &mut rng,
+                true,
+                true
+            ) > 0.
+        );
+
+        // Cold annealing only accepts moves which do not lengthen the tour
+        let mut cities_idx = initial.cities.clone();
+        let mut last = initial.cost;
+        let mut observer = |event: &Event| match event {
+            Event::TwoOptMove { cities_idx, .. } | Event::OrOptMove { cities_idx, .. } => {
+                let cost = instance.tour_length(cities_idx);
+                assert!(cost <= last);
+                last = cost;
+            }
+            _ => {}
+        };
+        SimulatedAnnealing::new()
+            .initial_temperature(1e-9)
+            .min_temperature(0.)
+            .iterations(100_000)
+            .anneal(&mut observer, &instance, &mut cities_idx, &mut rng, None);
+        assert!(instance.tour_length(&cities_idx) < initial.cost);
+
+        // Stops at once when the temperature is below the minimum
+        let mut cities_idx = initial.cities.clone();
+        SimulatedAnnealing::new()
+            .initial_temperature(1.)
+            .min_temperature(2.)
+            .anneal(
+                &mut NullObserver,
+                &instance,
+                &mut cities_idx,
+                &mut rng,
+                None,
+            );
+        assert_eq!(cities_idx, initial.cities);
+    }
+
+    #[test]
+    fn deadline() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let start = Instant::now();
+        let solution = Pipeline::new(Greedy)
+            .then(
+                SimulatedAnnealing::new()
+                    .iterations(usize::MAX)
+                    .cooling(Cooling::Adaptive {
+                        rate: 0.9999,
+                        stall: 10_000,
+                        reheat: 0.5,
+                    }),
+            )
+            .time_limit(Duration::from_millis(200))
+            .solve(&instance);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+    }
+}
@@ -0,0 +1,372 @@
+use crate::bounds;
+use crate::metric::{Cost, Metric};
+use crate::observer::{Event, NullObserver, Observer};
+use crate::tour::Tour;
+use rand::{rngs::StdRng, SeedableRng};
+use std::time::{Duration, Instant};
+
+// Tour found by a solver.
+// cities is an open permutation of 0, 1, ..., len - 1 and cost is its cycle length.
+// seed reproduces the same solution if the solver uses random numbers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub cities: Vec<usize>,
+    pub cost: Cost,
+    pub seed: Option<u64>,
+}
+
+impl Solution {
+    pub fn new(metric: &dyn Metric, cities: Vec<usize>) -> Solution {
+        let cost = metric.tour_length(&cities);
+        Solution {
+            cities,
+            cost,
+            seed: None,
+        }
+    }
+
+    // Held-Karp lower bound of the optimal length, None if distances are asymmetric.
+    // It takes O(n^2) time and memory for each of bounds::iterations steps.
+    pub fn lower_bound(&self, metric: &dyn Metric) -> Option<Cost> {
+        if !metric.is_symmetric() {
+            return None;
+        }
+        let iterations = bounds::iterations(metric.len());
+        Some(bounds::held_karp(metric, self.cost, iterations))
+    }
+
+    // (cost - lower bound) / lower bound with the bound of lower_bound
+    pub fn gap(&self, metric: &dyn Metric) -> Option<f64> {
+        self.lower_bound(metric)
+            .map(|lower_bound| bounds::gap(self.cost, lower_bound))
+    }
+
+    pub fn to_tour(&self, name: &str) -> Tour {
+        let mut tour = Tour::new(name, self.cities.clone());
+        tour.comment = format!("Length {}", self.cost);
+        if let Some(seed) = self.seed {
+            tour.comment.push_str(&format!("\nSeed {}", seed));
+        }
+        tour
+    }
+}
+
+// Algorithm finding a tour visiting all cities of a metric.
+// solve takes &mut self so solvers can keep state such as random number generators.
+pub trait Solver {
+    // Solve reporting progress to observer
+    fn solve_observed(&mut self, metric: &dyn Metric, observer: &mut dyn Observer) -> Solution;
+
+    fn solve(&mut self, metric: &dyn Metric) -> Solution {
+        self.solve_observed(metric, &mut NullObserver)
+    }
+}
+
+impl<S: Solver + ?Sized> Solver for Box<S> {
+    fn solve_observed(&mut self, metric: &dyn Metric, observer: &mut dyn Observer) -> Solution {
+        (**self).solve_observed(metric, observer)
+    }
+}
+
+// Heuristic building a tour from scratch such as greedy or nearest neighbor
+pub trait ConstructionHeuristic {
+    fn construct(&mut self, metric: &dyn Metric, observer: &mut dyn Observer) -> Vec<usize>;
+}
+
+// Heuristic improving a tour in place such as 2-opt.
+// It should return early once deadline has passed and take all random numbers from rng.
+pub trait LocalSearch {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        rng: &mut StdRng,
+        observer: &mut dyn Observer,
+    );
+
+    // Keep data depending only on metric such as neighbor lists for the calls of improve
+    // until finish. Metaheuristics running a local search many times on the same metric
+    // such as iterated local search call them around the runs.
+    fn prepare(&mut self, _: &dyn Metric) {}
+
+    fn finish(&mut self) {}
+}
+
+impl<L: LocalSearch + ?Sized> LocalSearch for Box<L> {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        rng: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        (**self).improve(metric, cities_idx, deadline, rng, observer)
+    }
+
+    fn prepare(&mut self, metric: &dyn Metric) {
+        (**self).prepare(metric)
+    }
+
+    fn finish(&mut self) {
+        (**self).finish()
+    }
+}
+
+// Solver building a tour with constructor and improving it with improvers in order.
+// time_limit and random numbers are shared by all stages.
+// The shortest tour after any stage is returned.
+// Without seed a random one is chosen and recorded in the solution.
+pub struct Pipeline {
+    constructor: Box<dyn ConstructionHeuristic>,
+    improvers: Vec<Box<dyn LocalSearch>>,
+    time_limit: Option<Duration>,
+    seed: Option<u64>,
+}
+
+impl Pipeline {
+    pub fn new<C: ConstructionHeuristic + 'static>(constructor: C) -> Pipeline {
+        Pipeline {
+            constructor: Box::new(constructor),
+            improvers: vec![],
+            time_limit: None,
+            seed: None,
+        }
+    }
+
+    pub fn then<L: LocalSearch + 'static>(mut self, improver: L) -> Pipeline {
+        self.improvers.push(Box::new(improver));
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> Pipeline {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Pipeline {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl Solver for Pipeline {
+    fn solve_observed(&mut self, metric: &dyn Metric, observer: &mut dyn Observer) -> Solution {
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut cities_idx = self.constructor.construct(metric, observer);
+        let mut best = metric.tour_length(&cities_idx);
+        observer.notify(&Event::NewBest {
+            cities_idx: &cities_idx,
+            cost: best,
+        });
+        // Stages such as simulated annealing may leave a longer tour than an earlier one
+        let mut best_cities = cities_idx.clone();
+
+        for improver in self.improvers.iter_mut() {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                break;
+            }
+            improver.improve(metric, &mut cities_idx, deadline, &mut rng, observer);
+
+            // Tours as short as the best one replace it so later stages take effect
+            let cost = metric.tour_length(&cities_idx);
+            if cost <= best {
+                best_cities.copy_from_slice(&cities_idx);
+            }
+            if cost < best {
+                best = cost;
+                observer.notify(&Event::NewBest {
+                    cities_idx: &cities_idx,
+                    cost,
+                });
+            }
+        }
+
+        Solution {
+            cities: best_cities,
+            cost: best,
+            seed: Some(seed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        branch_and_bound::BranchAndBound,
+        christofides::Christofides,
+        common::TSP_FILE_BERLIN52,
+        greedy::Greedy,
+        held_karp::HeldKarp,
+        lin_kernighan::LinKernighan,
+        matrix::DistanceMatrix,
+        metric::{CoordMetric, EdgeWeightType},
+        nearest_insertion::NearestInsertion,
+        nearest_neighbor::NearestNeighbor,
+        or_opt::OrOpt,
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+
+    struct Identity;
+
+    impl Solver for Identity {
+        fn solve_observed(&mut self, metric: &dyn Metric, _: &mut dyn Observer) -> Solution {
+            Solution::new(metric, (0..metric.len()).collect())
+        }
+    }
+
+    #[test]
+    fn solution() {
+        let matrix = DistanceMatrix::from_fn(3, |i, j| (i + j) as Cost);
+        let mut solver: Box<dyn Solver> = Box::new(Identity);
+        let solution = solver.solve(&matrix);
+        assert_eq!(solution.cities, vec![0, 1, 2]);
+        assert_eq!(solution.cost, 1 + 3 + 2);
+
+        let tour = solution.to_tour("tiny.tour");
+        assert_eq!(tour.cities, solution.cities);
+        assert_eq!(tour.comment, "Length 6");
+
+        let solution = Solution {
+            seed: Some(7),
+            ..solution
+        };
+        assert_eq!(solution.to_tour("tiny.tour").comment, "Length 6\nSeed 7");
+    }
+
+    #[test]
+    fn gap() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let solution = Pipeline::new(Greedy).solve(&instance);
+        // Held-Karp bound of berlin52 is the optimum
+        assert_eq!(solution.lower_bound(&instance), Some(7542));
+        let gap = solution.gap(&instance).unwrap();
+        assert_eq!(gap, (solution.cost - 7542) as f64 / 7542.);
+
+        // No bound for asymmetric distances
+        let matrix = DistanceMatrix::from_fn(3, |i, j| if (i + 1) % 3 == j { 1 } else { 2 });
+        let solution = Identity.solve(&matrix);
+        assert_eq!(solution.lower_bound(&matrix), None);
+        assert_eq!(solution.gap(&matrix), None);
+    }
+
+    // Improver reversing the tour, which does not change its length
+    struct Reverse;
+
+    impl LocalSearch for Reverse {
+        fn improve(
+            &mut self,
+            _: &dyn Metric,
+            cities_idx: &mut [usize],
+            _: Option<Instant>,
+            _: &mut StdRng,
+            _: &mut dyn Observer,
+        ) {
+            cities_idx.reverse();
+        }
+    }
+
+    // Improver making the tour longer by exchanging the second and the last cities
+    struct Worsen;
+
+    impl LocalSearch for Worsen {
+        fn improve(
+            &mut self,
+            _: &dyn Metric,
+            cities_idx: &mut [usize],
+            _: Option<Instant>,
+            _: &mut StdRng,
+            _: &mut dyn Observer,
+        ) {
+            let last = cities_idx.len() - 1;
+            cities_idx.swap(1, last);
+        }
+    }
+
+    #[test]
+    fn pipeline() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let greedy = Greedy.construct(&instance, &mut NullObserver);
+
+        let solution = Pipeline::new(Greedy).solve(&instance);
+        assert_eq!(solution.cities, greedy);
+
+        // Improvers run in the order they are given
+        let solution = Pipeline::new(Greedy)
+            .then(Reverse)
+            .then(Reverse)
+            .then(Reverse)
+            .solve(&instance);
+        let mut reversed = greedy.clone();
+        reversed.reverse();
+        assert_eq!(solution.cities, reversed);
+
+        let mut pipeline = Pipeline::new(Greedy)
+            .then(TwoOpt::new())
+            .then(Reverse)
+            .time_limit(Duration::from_millis(100));
+        let solution = pipeline.solve(&instance);
+        assert!(solution.cost <= instance.tour_length(&greedy));
+
+        // Costs reported to observer only decrease and the last one is the result
+        let mut costs = vec![];
+        let solution = Pipeline::new(Greedy)
+            .then(TwoOpt::new())
+            .time_limit(Duration::from_millis(100))
+            .solve_observed(&instance, &mut |event: &Event| {
+                if let Event::NewBest { cost, .. } = event {
+                    costs.push(*cost);
+                }
+            });
+        assert!(costs.windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(costs.last(), Some(&solution.cost));
+
+        // The shortest tour of all stages is returned with its own length
+        let two_opt = Pipeline::new(Greedy)
+            .then(TwoOpt::new())
+            .seed(0)
+            .solve(&instance);
+        let solution = Pipeline::new(Greedy)
+            .then(TwoOpt::new())
+            .then(Worsen)
+            .seed(0)
+            .solve(&instance);
+        assert_eq!(solution.cities, two_opt.cities);
+        assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+    }
+
+    // Instances with less than three cities have only one tour, which every solver finds
+    #[test]
+    fn tiny() {
+        let coords = [(0., 0.), (3., 4.)];
+        for city_len in 0..=2 {
+            let metric = CoordMetric::new(coords[..city_len].to_vec(), EdgeWeightType::Euc2d);
+
+            let pipelines = [
+                Pipeline::new(Greedy),
+                Pipeline::new(NearestNeighbor),
+                Pipeline::new(NearestInsertion),
+                Pipeline::new(Christofides),
+                Pipeline::new(HeldKarp),
+                Pipeline::new(BranchAndBound::new()),
+            ];
+            for pipeline in pipelines {
+                let mut solution = pipeline
+                    .then(TwoOpt::new())
+                    .then(OrOpt::new())
+                    .then(LinKernighan::new())
+                    .seed(0)
+                    .solve(&metric);
+                solution.cities.sort();
+                assert_eq!(solution.cities, (0..city_len).collect::<Vec<usize>>());
+                assert_eq!(solution.cost, 10 * (city_len as Cost / 2));
+            }
+        }
+    }
+}
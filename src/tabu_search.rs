@@ -0,0 +1,535 @@
+use rand::rngs::StdRng;
+
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+use crate::observer::{Event, Observer};
+use crate::solver::LocalSearch;
+use crate::two_opt::NEIGHBORS;
+use std::time::Instant;
+
+// Number of moves by default
+pub const ITERATIONS: usize = 10_000;
+// Number of moves during which edges removed by a move stay tabu by default
+pub const TENURE: usize = 40;
+
+// Tabu search over 2-opt moves and swaps of two cities. Every iteration applies the best
+// move adding an edge between a city and one of its neighbors even if it makes the tour
+// longer. Edges removed by a move are tabu for the next tenure moves, which means moves
+// adding them back are skipped, so the search does not go back to the local optimum it
+// just left. A tabu move is still allowed when it gives a tour shorter than the best one
+// found (aspiration). The shortest tour found is returned.
+// Tabu status is kept for all pairs of cities, which takes O(n^2) memory.
+#[derive(Debug, Clone)]
+pub struct TabuSearch {
+    tenure: usize,
+    iterations: usize,
+    neighbors: usize,
+}
+
+impl Default for TabuSearch {
+    fn default() -> TabuSearch {
+        TabuSearch {
+            tenure: TENURE,
+            iterations: ITERATIONS,
+            neighbors: NEIGHBORS,
+        }
+    }
+}
+
+impl TabuSearch {
+    pub fn new() -> TabuSearch {
+        TabuSearch::default()
+    }
+
+    pub fn tenure(mut self, tenure: usize) -> TabuSearch {
+        self.tenure = tenure;
+        self
+    }
+
+    // Number of moves. The deadline of the pipeline also stops the search.
+    pub fn iterations(mut self, iterations: usize) -> TabuSearch {
+        self.iterations = iterations;
+        self
+    }
+
+    // Number of nearest neighbors of each city tried as new edges
+    pub fn neighbors(mut self, k: usize) -> TabuSearch {
+        self.neighbors = k;
+        self
+    }
+}
+
+impl LocalSearch for TabuSearch {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        _: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        let neighbors = NeighborLists::new(metric, self.neighbors);
+        solver(
+            observer,
+            metric,
+            cities_idx,
+            &neighbors,
+            self.tenure,
+            self.iterations,
+            deadline,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    // Reverse the part of the cycle from position from to position to,
+    // which may wrap around the end of cities_idx
+    TwoOpt { from: usize, to: usize },
+    // Exchange the cities at positions i and j
+    Swap { i: usize, j: usize },
+}
+
+// Move with the edges it removes from and adds to the tour
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    kind: Kind,
+    delta: Cost,
+    removed: [(usize, usize); 4],
+    added: [(usize, usize); 4],
+    len: usize,
+}
+
+impl Move {
+    fn new<M: Metric + ?Sized>(
+        metric: &M,
+        kind: Kind,
+        removed: &[(usize, usize)],
+        added: &[(usize, usize)],
+    ) -> Move {
+        let length = |edges: &[(usize, usize)]| -> Cost {
+            edges.iter().map(|(a, b)| metric.dist(*a, *b)).sum()
+        };
+
+        let mut m = Move {
+            kind,
+            delta: length(added) - length(removed),
+            removed: [(0, 0); 4],
+            added: [(0, 0); 4],
+            len: removed.len(),
+        };
+        m.removed[..removed.len()].copy_from_slice(removed);
+        m.added[..added.len()].copy_from_slice(added);
+        m
+    }
+}
+
+// Run tabu search and leave the shortest tour found in cities_idx.
+// Returns the convergence trace, the pairs of the number of moves and the best length
+// which start with the length of the given tour and have an entry for every new best tour.
+// Each entry is also reported by Event::TabuBest.
+// Tours of asymmetric metrics are left unchanged since 2-opt deltas assume reversed
+// parts of the tour keep their length.
+pub fn solver<M: Metric + ?Sized>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    neighbors: &NeighborLists,
+    tenure: usize,
+    iterations: usize,
+    deadline: Option<Instant>,
+) -> Vec<(usize, Cost)> {
+    let city_len = cities_idx.len();
+    let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+    let mut cost = metric.tour_length(cities_idx);
+    let mut trace = vec![(0, cost)];
+    observer.notify(&Event::TabuBest { iteration: 0, cost });
+    // Swaps need two cities between the swapped ones on one side
+    if city_len < 5 || !neighbors.is_symmetric() {
+        return trace;
+    }
+
+    // Position of each city in cities_idx
+    let mut pos = vec![0; city_len];
+    for (i, city) in cities_idx.iter().enumerate() {
+        pos[*city] = i;
+    }
+
+    // Number of moves after which each edge is no longer tabu
+    let mut tabu_until = vec![0; city_len * city_len];
+
+    let mut best = cities_idx.to_vec();
+    let mut best_cost = cost;
+
+    for iteration in 1..=iterations {
+        if expired() {
+            break;
+        }
+
+        let allowed = |m: &Move| {
+            let tabu = |(a, b): &(usize, usize)| tabu_until[a * city_len + b] >= iteration;
+            cost + m.delta < best_cost || !m.added[..m.len].iter().any(tabu)
+        };
+        let Some(m) = best_move(metric, cities_idx, &pos, neighbors, allowed) else {
+            // Every move is tabu
+            break;
+        };
+
+        for (a, b) in m.removed[..m.len].iter().copied() {
+            tabu_until[a * city_len + b] = iteration + tenure;
+            tabu_until[b * city_len + a] = iteration + tenure;
+        }
+        apply(observer, cities_idx, &mut pos, m.kind);
+        cost += m.delta;
+
+        if cost < best_cost {
+            best_cost = cost;
+            best.copy_from_slice(cities_idx);
+            trace.push((iteration, cost));
+            observer.notify(&Event::TabuBest { iteration, cost });
+        }
+    }
+
+    cities_idx.copy_from_slice(&best);
+    trace
+}
+
+// Find the shortest allowed move adding an edge between a city and one of its neighbors
+fn best_move<M: Metric + ?Sized>(
+    metric: &M,
+    cities_idx: &[usize],
+    pos: &[usize],
+    neighbors: &NeighborLists,
+    allowed: impl Fn(&Move) -> bool,
+) -> Option<Move> {
+    let city_len = cities_idx.len();
+    let succ = |city: usize| cities_idx[(pos[city] + 1) % city_len];
+    let pred = |city: usize| cities_idx[(pos[city] + city_len - 1) % city_len];
+
+    let mut best: Option<Move> = None;
+    let mut consider = |m: Move| {
+        if best.is_none_or(|b| m.delta < b.delta) && allowed(&m) {
+            best = Some(m);
+        }
+    };
+
+    for a in 0..city_len {
+        let (pa, sa) = (pred(a), succ(a));
+
+        for c in neighbors.get(a).iter().copied() {
+            // Replace (a, sa) and (c, sc) with (a, c) and (sa, sc)
+            let sc = succ(c);
+            if c != sa && sc != a {
+                let kind = Kind::TwoOpt {
+                    from: pos[sa],
+                    to: pos[c],
+                };
+                consider(Move::new(
+                    metric,
+                    kind,
+                    &[(a, sa), (c, sc)],
+                    &[(a, c), (sa, sc)],
+                ));
+            }
+
+            // Replace (pa, a) and (pc, c) with (a, c) and (pa, pc)
+            let pc = pred(c);
+            if c != pa && pc != a {
+                let kind = Kind::TwoOpt {
+                    from: pos[a],
+                    to: pos[pc],
+                };
+                consider(Move::new(
+                    metric,
+                    kind,
+                    &[(pa, a), (pc, c)],
+                    &[(a, c), (pa, pc)],
+                ));
+            }
+
+            // Swap c with sa or pa so that c comes next to a
+            for x in [sa, pa] {
+                if x != c {
+                    consider(swap(metric, cities_idx, pos[x], pos[c]));
+                }
+            }
+        }
+    }
+
+    best
+}
+
+// Move exchanging the cities at positions i and j
+fn swap<M: Metric + ?Sized>(metric: &M, cities_idx: &[usize], i: usize, j: usize) -> Move {
+    let city_len = cities_idx.len();
+    let kind = Kind::Swap { i, j };
+    let at = |k: usize| cities_idx[k % city_len];
+
+    // Order i and j so that j is at most two positions after i if they are that close
+    let close = |i: usize, j: usize| (j + city_len - i) % city_len <= 2;
+    let (i, j) = if close(i, j) || !close(j, i) {
+        (i, j)
+    } else {
+        (j, i)
+    };
+    let (p, x, s) = (at(i + city_len - 1), at(i), at(i + 1));
+    let (q, y, t) = (at(j + city_len - 1), at(j), at(j + 1));
+
+    if close(i, j) {
+        // p x y t or p x s y t becomes p y x t or p y s x t keeping the edges in between
+        Move::new(metric, kind, &[(p, x), (y, t)], &[(p, y), (x, t)])
+    } else {
+        Move::new(
+            metric,
+            kind,
+            &[(p, x), (x, s), (q, y), (y, t)],
+            &[(p, y), (y, s), (q, x), (x, t)],
+        )
+    }
+}
+
+fn apply(observer: &mut dyn Observer, cities_idx: &mut [usize], pos: &mut [usize], kind: Kind) {
+    match kind {
+        Kind::TwoOpt { from, to } => {
+            // Reversing the rest of the cycle gives the same cycle in the opposite direction
+            let (i, j) = if from <= to {
+                (from, to)
+            } else {
+                (to + 1, from - 1)
+            };
+
+            cities_idx[i..=j].reverse();
+            for (k, city) in cities_idx[i..=j].iter().enumerate() {
+                pos[*city] = i + k;
+            }
+
+            observer.notify(&Event::TwoOptMove { i, j, cities_idx });
+        }
+        Kind::Swap { i, j } => {
+            cities_idx.swap(i, j);
+            pos[cities_idx[i]] = i;
+            pos[cities_idx[j]] = j;
+
+            observer.notify(&Event::SwapMove { i, j, cities_idx });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100},
+        greedy::{self, Greedy},
+        metric::FnMetric,
+        nearest_insertion, nearest_neighbor,
+        observer::NullObserver,
+        solver::{Pipeline, Solver},
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    #[test]
+    fn moves() {
+        let x = [0, 3, 1, 7, 4, 9, 2];
+        let metric = FnMetric::new(7, |i, j| (x[i] as Cost - x[j] as Cost).abs());
+        let initial = (0..7).collect::<Vec<usize>>();
+
+        // Swaps of neighboring cities, cities two apart, far apart and around the end
+        for (i, j) in [(2, 3), (3, 2), (1, 3), (1, 4), (0, 6), (6, 1), (0, 5)] {
+            let mut cities_idx = initial.clone();
+            let mut pos = initial.clone();
+            let m = swap(&metric, &cities_idx, i, j);
+            apply(&mut NullObserver, &mut cities_idx, &mut pos, m.kind);
+            assert_eq!(
+                metric.tour_length(&cities_idx),
+                metric.tour_length(&initial) + m.delta,
+                "swap {} {}",
+                i,
+                j
+            );
+            // Cities next to each other or two apart change only two edges
+            let gap = j.abs_diff(i).min(7 - j.abs_diff(i));
+            assert_eq!(m.len, if gap <= 2 { 2 } else { 4 });
+        }
+
+        // Every candidate move has the delta of the tour it gives
+        let neighbors = NeighborLists::new(&metric, 3);
+        let mut cities_idx = initial.clone();
+        let mut pos = initial.clone();
+        for _ in 0..20 {
+            let cost = metric.tour_length(&cities_idx);
+            let m = best_move(&metric, &cities_idx, &pos, &neighbors, |_| true).unwrap();
+            apply(&mut NullObserver, &mut cities_idx, &mut pos, m.kind);
+            assert_eq!(metric.tour_length(&cities_idx), cost + m.delta);
+            for (i, city) in cities_idx.iter().enumerate() {
+                assert_eq!(pos[*city], i);
+            }
+        }
+    }
+
+    // Tabu search gets past the 2-opt local optimum from any constructor
+    #[test]
+    fn constructors() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+
+            for constructor in [
+                greedy::solver,
+                nearest_neighbor::solver,
+                nearest_insertion::solver,
+            ] {
+                let mut cities_idx = constructor(&mut NullObserver, &instance);
+                let initial = instance.tour_length(&cities_idx);
+                let mut two_opt = cities_idx.clone();
+                TwoOpt::new().improve(
+                    &instance,
+                    &mut two_opt,
+                    None,
+                    &mut StdRng::seed_from_u64(0),
+                    &mut NullObserver,
+                );
+
+                let trace = solver(
+                    &mut NullObserver,
+                    &instance,
+                    &mut cities_idx,
+                    &neighbors,
+                    TENURE,
+                    1000,
+                    None,
+                );
+                let cost = instance.tour_length(&cities_idx);
+                println!(
+                    "{}: initial {} 2-opt {} tabu {}",
+                    tsp_file,
+                    initial,
+                    instance.tour_length(&two_opt),
+                    cost
+                );
+                assert!(cost < instance.tour_length(&two_opt));
+
+                // The trace starts from the given tour and ends with the returned one
+                assert_eq!(trace[0], (0, initial));
+                assert_eq!(trace.last().unwrap().1, cost);
+                assert!(trace.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 > w[1].1));
+
+                let mut sorted = cities_idx.clone();
+                sorted.sort();
+                assert_eq!(sorted, (0..instance.len()).collect::<Vec<usize>>());
+            }
+        }
+    }
+
+    // Without the tabu list the search goes back to the local optimum it just left
+    #[test]
+    fn tenure() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let initial = nearest_neighbor::solver(&mut NullObserver, &instance);
+
+        let costs = [0, TENURE].map(|tenure| {
+            let mut cities_idx = initial.clone();
+            let trace = solver(
+                &mut NullObserver,
+                &instance,
+                &mut cities_idx,
+                &neighbors,
+                tenure,
+                1000,
+                None,
+            );
+            println!("tenure {}: {:?}", tenure, trace.last());
+            trace.last().unwrap().1
+        });
+        assert!(costs[1] < costs[0]);
+    }
+
+    // Pipelines get the same trace from the observer
+    #[test]
+    fn trace_events() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let mut cities_idx = greedy::solver(&mut NullObserver, &instance);
+        let trace = solver(
+            &mut NullObserver,
+            &instance,
+            &mut cities_idx,
+            &neighbors,
+            TENURE,
+            1000,
+            None,
+        );
+
+        let mut events = vec![];
+        let solution = Pipeline::new(Greedy)
+            .then(TabuSearch::new().iterations(1000))
+            .solve_observed(&instance, &mut |event: &Event| {
+                if let Event::TabuBest { iteration, cost } = event {
+                    events.push((*iteration, *cost));
+                }
+            });
+        assert_eq!(events, trace);
+        assert_eq!(solution.cost, trace.last().unwrap().1);
+    }
+
+    // Deltas are wrong for asymmetric distances, so the tour is kept
+    #[test]
+    fn asymmetric() {
+        for seed in 0..5 {
+            let metric = random_asymmetric(30, seed);
+            let initial = greedy::solver(&mut NullObserver, &metric);
+
+            let mut events = vec![];
+            let mut cities_idx = initial.clone();
+            TabuSearch::new().improve(
+                &metric,
+                &mut cities_idx,
+                None,
+                &mut StdRng::seed_from_u64(0),
+                &mut |event: &Event| {
+                    if let Event::TabuBest { iteration, cost } = event {
+                        events.push((*iteration, *cost));
+                    }
+                },
+            );
+            assert_eq!(cities_idx, initial);
+            assert_eq!(events, vec![(0, metric.tour_length(&initial))]);
+        }
+    }
+
+    #[test]
+    fn budget() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+        let initial = greedy::solver(&mut NullObserver, &instance);
+
+        // Expired deadline stops before any move
+        let mut cities_idx = initial.clone();
+        let trace = solver(
+            &mut NullObserver,
+            &instance,
+            &mut cities_idx,
+            &neighbors,
+            TENURE,
+            ITERATIONS,
+            Some(Instant::now()),
+        );
+        assert_eq!(trace, vec![(0, instance.tour_length(&initial))]);
+        assert_eq!(cities_idx, initial);
+
+        // The best tour is returned when the time limit stops the search
+        let start = Instant::now();
+        let solution = Pipeline::new(Greedy)
+            .then(TabuSearch::new().iterations(usize::MAX))
+            .time_limit(Duration::from_millis(200))
+            .solve(&instance);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(solution.cost < instance.tour_length(&initial));
+        assert_eq!(solution.cost, instance.tour_length(&solution.cities));
+    }
+}
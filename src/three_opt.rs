@@ -0,0 +1,533 @@
+use rand::rngs::StdRng;
+
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+pub use crate::observer::Reconnection;
+use crate::observer::{Event, Observer};
+use crate::solver::LocalSearch;
+use crate::two_opt::NEIGHBORS;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+
+// Reconnections tried by ThreeOpt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MoveSet {
+    // All reconnections including 2-opt moves
+    #[default]
+    Full,
+    // Only Exchange, which is called or-3opt. No segment is reversed so it suits
+    // asymmetric distances and is cheaper than full 3-opt.
+    OrThreeOpt,
+}
+
+impl MoveSet {
+    fn reconnections(&self) -> &'static [Reconnection] {
+        match self {
+            MoveSet::Full => &Reconnection::ALL,
+            MoveSet::OrThreeOpt => &[Reconnection::Exchange],
+        }
+    }
+}
+
+// Number of applied moves of each reconnection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MoveCounts {
+    counts: [usize; 7],
+}
+
+impl MoveCounts {
+    pub fn get(&self, reconnection: Reconnection) -> usize {
+        self.counts[reconnection as usize]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    pub fn add(&mut self, reconnection: Reconnection) {
+        self.counts[reconnection as usize] += 1;
+    }
+}
+
+impl fmt::Display for MoveCounts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, reconnection) in Reconnection::ALL.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", reconnection.name(), self.get(*reconnection))?;
+        }
+        Ok(())
+    }
+}
+
+// Local search replacing three edges while it shortens the tour.
+// Removed edges are chosen so that new edges connect nearest neighbors and cities whose
+// tour neighbors have not changed since the last failed search are skipped (don't-look bits).
+// All triples of edges are checked at the end, so no improving move remains when it returns
+// unless deadline has passed. The check takes O(n^3) time.
+#[derive(Debug, Clone)]
+pub struct ThreeOpt {
+    moves: MoveSet,
+    neighbors: usize,
+    // Neighbor lists kept between prepare and finish
+    prepared: Option<NeighborLists>,
+}
+
+impl Default for ThreeOpt {
+    fn default() -> ThreeOpt {
+        ThreeOpt {
+            moves: MoveSet::default(),
+            neighbors: NEIGHBORS,
+            prepared: None,
+        }
+    }
+}
+
+impl ThreeOpt {
+    pub fn new() -> ThreeOpt {
+        ThreeOpt::default()
+    }
+
+    pub fn moves(mut self, moves: MoveSet) -> ThreeOpt {
+        self.moves = moves;
+        self
+    }
+
+    // Number of nearest neighbors of each city tried as new edges
+    pub fn neighbors(mut self, k: usize) -> ThreeOpt {
+        self.neighbors = k;
+        self
+    }
+}
+
+impl LocalSearch for ThreeOpt {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        _: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        let neighbors = NeighborLists::prepared_or_new(&self.prepared, metric, self.neighbors);
+        local_optimum(
+            observer, metric, cities_idx, &neighbors, self.moves, deadline,
+        );
+    }
+
+    fn prepare(&mut self, metric: &dyn Metric) {
+        self.prepared = Some(NeighborLists::new(metric, self.neighbors));
+    }
+
+    fn finish(&mut self) {
+        self.prepared = None;
+    }
+}
+
+// Move removing the edges starting at positions i, i + first and i + second (mod n)
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    delta: Cost,
+    i: usize,
+    first: usize,
+    second: usize,
+    reconnection: Reconnection,
+}
+
+// Apply improving moves until none remains and return the number of applied moves.
+// Asymmetric metrics only get OrThreeOpt moves since deltas of the other reconnections
+// ignore the changed length of reversed segments.
+pub fn local_optimum<M: Metric + ?Sized>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    neighbors: &NeighborLists,
+    moves: MoveSet,
+    deadline: Option<Instant>,
+) -> MoveCounts {
+    let city_len = cities_idx.len();
+    let mut counts = MoveCounts::default();
+    // Every tour of three cities is optimal
+    if city_len < 4 {
+        return counts;
+    }
+    let moves = if neighbors.is_symmetric() {
+        moves
+    } else {
+        MoveSet::OrThreeOpt
+    };
+
+    let mut pos = vec![0; city_len];
+    for (i, city) in cities_idx.iter().enumerate() {
+        pos[*city] = i;
+    }
+
+    // Cities to search from. A city is queued again when its tour neighbors change.
+    let mut queue = (0..city_len).collect::<VecDeque<usize>>();
+    let mut queued = vec![true; city_len];
+
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return counts;
+        }
+
+        let found = match queue.pop_front() {
+            Some(a) => {
+                queued[a] = false;
+                match search_neighbors(metric, cities_idx, &pos, neighbors, moves, a) {
+                    Some(m) => m,
+                    None => continue,
+                }
+            }
+            // All cities look fine so check all triples of edges
+            None => match search_all(metric, cities_idx, moves) {
+                Some(m) => m,
+                None => return counts,
+            },
+        };
+
+        let endpoints = apply(observer, cities_idx, &mut pos, found);
+        counts.add(found.reconnection);
+        for city in endpoints {
+            if !queued[city] {
+                queued[city] = true;
+                queue.push_back(city);
+            }
+        }
+    }
+}
+
+// Find the best move removing an edge at city a and adding edges to the nearest neighbors
+// of the ends of the removed edge
+fn search_neighbors<M: Metric + ?Sized>(
+    metric: &M,
+    cities_idx: &[usize],
+    pos: &[usize],
+    neighbors: &NeighborLists,
+    moves: MoveSet,
+    a: usize,
+) -> Option<Move> {
+    let city_len = cities_idx.len();
+    // Edges at city: the edge to its successor and the edge from its predecessor
+    let edges = |city: usize| [pos[city], (pos[city] + city_len - 1) % city_len];
+
+    let mut best: Option<Move> = None;
+
+    for i in edges(a) {
+        let (u, v) = (cities_idx[i], cities_idx[(i + 1) % city_len]);
+
+        for x in neighbors.get(u).iter() {
+            for y in neighbors.get(v).iter() {
+                let mut candidates = [0; 4];
+                candidates[..2].copy_from_slice(&edges(*x));
+                candidates[2..].copy_from_slice(&edges(*y));
+
+                for (k, p) in candidates.iter().enumerate() {
+                    for q in candidates[k + 1..].iter() {
+                        if *p == i || *q == i || p == q {
+                            continue;
+                        }
+
+                        // Offsets of the other edges from edge i
+                        let (mut first, mut second) =
+                            ((p + city_len - i) % city_len, (q + city_len - i) % city_len);
+                        if first > second {
+                            std::mem::swap(&mut first, &mut second);
+                        }
+
+                        if let Some(m) = evaluate(metric, cities_idx, moves, i, first, second) {
+                            if best.is_none_or(|b| m.delta < b.delta) {
+                                best = Some(m);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    best
+}
+
+// Find the first improving move among all triples of edges
+fn search_all<M: Metric + ?Sized>(
+    metric: &M,
+    cities_idx: &[usize],
+    moves: MoveSet,
+) -> Option<Move> {
+    let city_len = cities_idx.len();
+
+    for first in 1..city_len - 1 {
+        for second in first + 1..city_len {
+            // Each triple of edges is tried once from its first edge in the array
+            for i in 0..city_len - second {
+                if let Some(m) = evaluate(metric, cities_idx, moves, i, first, second) {
+                    return Some(m);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Best improving reconnection after removing the edges starting at
+// positions i, i + first and i + second (mod n) where 0 < first < second < n
+fn evaluate<M: Metric + ?Sized>(
+    metric: &M,
+    cities_idx: &[usize],
+    moves: MoveSet,
+    i: usize,
+    first: usize,
+    second: usize,
+) -> Option<Move> {
+    let city_len = cities_idx.len();
+    let at = |offset: usize| cities_idx[(i + offset) % city_len];
+    let (a, b) = (at(0), at(1));
+    let (c, d) = (at(first), at(first + 1));
+    let (e, f) = (at(second), at(second + 1));
+    let dist = |x: usize, y: usize| metric.dist(x, y);
+
+    let removed = dist(a, b) + dist(c, d) + dist(e, f);
+
+    let mut best: Option<Move> = None;
+    for reconnection in moves.reconnections() {
+        let added = match reconnection {
+            Reconnection::ReverseFirst => dist(a, c) + dist(b, d) + dist(e, f),
+            Reconnection::ReverseSecond => dist(a, b) + dist(c, e) + dist(d, f),
+            Reconnection::ReverseBoth => dist(a, e) + dist(d, c) + dist(b, f),
+            Reconnection::ReverseEach => dist(a, c) + dist(b, e) + dist(d, f),
+            Reconnection::Exchange => dist(a, d) + dist(e, b) + dist(c, f),
+            Reconnection::ExchangeReverseFirst => dist(a, d) + dist(e, c) + dist(b, f),
+            Reconnection::ExchangeReverseSecond => dist(a, e) + dist(d, b) + dist(c, f),
+        };
+
+        let delta = added - removed;
+        if delta < 0 && best.is_none_or(|m| delta < m.delta) {
+            best = Some(Move {
+                delta,
+                i,
+                first,
+                second,
+                reconnection: *reconnection,
+            });
+        }
+    }
+
+    best
+}
+
+// Apply move and return the ends of the removed edges
+fn apply(
+    observer: &mut dyn Observer,
+    cities_idx: &mut [usize],
+    pos: &mut [usize],
+    m: Move,
+) -> [usize; 6] {
+    let city_len = cities_idx.len();
+    let at = |offset: usize| (m.i + offset) % city_len;
+
+    let endpoints = [
+        cities_idx[at(0)],
+        cities_idx[at(1)],
+        cities_idx[at(m.first)],
+        cities_idx[at(m.first + 1)],
+        cities_idx[at(m.second)],
+        cities_idx[at(m.second + 1)],
+    ];
+
+    let mut s1 = (1..=m.first)
+        .map(|k| cities_idx[at(k)])
+        .collect::<Vec<usize>>();
+    let mut s2 = (m.first + 1..=m.second)
+        .map(|k| cities_idx[at(k)])
+        .collect::<Vec<usize>>();
+
+    let (reverse_first, reverse_second, exchange) = match m.reconnection {
+        Reconnection::ReverseFirst => (true, false, false),
+        Reconnection::ReverseSecond => (false, true, false),
+        // Reversing both segments and exchanging them reverses the whole part
+        Reconnection::ReverseBoth => (true, true, true),
+        Reconnection::ReverseEach => (true, true, false),
+        Reconnection::Exchange => (false, false, true),
+        Reconnection::ExchangeReverseFirst => (true, false, true),
+        Reconnection::ExchangeReverseSecond => (false, true, true),
+    };
+    if reverse_first {
+        s1.reverse();
+    }
+    if reverse_second {
+        s2.reverse();
+    }
+    let segments = if exchange { [s2, s1] } else { [s1, s2] };
+
+    for (k, city) in segments.iter().flatten().enumerate() {
+        let p = at(k + 1);
+        cities_idx[p] = *city;
+        pos[*city] = p;
+    }
+
+    observer.notify(&Event::ThreeOptMove {
+        reconnection: m.reconnection,
+        cities_idx,
+    });
+
+    endpoints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225},
+        greedy::Greedy,
+        metric::FnMetric,
+        observer::NullObserver,
+        or_opt::OrOpt,
+        solver::{Pipeline, Solver},
+        tsplib::Instance,
+        two_opt::TwoOpt,
+    };
+
+    // Each reconnection gives the tour written in its comment
+    #[test]
+    fn reconnections() {
+        // a b c d e f g h are 0 1 2 3 4 5 6 7 and edges (a, b), (c, d) and (f, g) are removed.
+        // So b..c = 1 2 and d..f = 3 4 5.
+        let expected = [
+            vec![0, 2, 1, 3, 4, 5, 6, 7],
+            vec![0, 1, 2, 5, 4, 3, 6, 7],
+            vec![0, 5, 4, 3, 2, 1, 6, 7],
+            vec![0, 2, 1, 5, 4, 3, 6, 7],
+            vec![0, 3, 4, 5, 1, 2, 6, 7],
+            vec![0, 3, 4, 5, 2, 1, 6, 7],
+            vec![0, 5, 4, 3, 1, 2, 6, 7],
+        ];
+
+        for (reconnection, expected) in Reconnection::ALL.iter().zip(expected) {
+            let mut cities_idx = (0..8).collect::<Vec<usize>>();
+            let mut pos = cities_idx.clone();
+            let m = Move {
+                delta: -1,
+                i: 0,
+                first: 2,
+                second: 5,
+                reconnection: *reconnection,
+            };
+            let endpoints = apply(&mut NullObserver, &mut cities_idx, &mut pos, m);
+            assert_eq!(cities_idx, expected, "{:?}", reconnection);
+            assert_eq!(endpoints, [0, 1, 2, 3, 5, 6]);
+            for (i, city) in cities_idx.iter().enumerate() {
+                assert_eq!(pos[*city], i);
+            }
+        }
+    }
+
+    // Delta computed by evaluate is the change of the tour length
+    #[test]
+    fn delta() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let initial = (0..instance.len()).collect::<Vec<usize>>();
+
+        for (i, first, second) in [(0, 1, 2), (3, 10, 30), (40, 5, 11), (51, 1, 50)] {
+            for moves in [MoveSet::Full, MoveSet::OrThreeOpt] {
+                if let Some(m) = evaluate(&instance, &initial, moves, i, first, second) {
+                    let mut cities_idx = initial.clone();
+                    let mut pos = initial.clone();
+                    apply(&mut NullObserver, &mut cities_idx, &mut pos, m);
+                    assert_eq!(
+                        instance.tour_length(&cities_idx),
+                        instance.tour_length(&initial) + m.delta
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn local_optimum() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let initial = Pipeline::new(Greedy).solve(&instance);
+            let neighbors = NeighborLists::new(&instance, NEIGHBORS);
+
+            for moves in [MoveSet::Full, MoveSet::OrThreeOpt] {
+                let mut cities_idx = initial.cities.clone();
+                let counts = super::local_optimum(
+                    &mut NullObserver,
+                    &instance,
+                    &mut cities_idx,
+                    &neighbors,
+                    moves,
+                    None,
+                );
+
+                let cost = instance.tour_length(&cities_idx);
+                assert!(cost < initial.cost);
+                assert!(counts.total() > 0);
+                assert!(search_all(&instance, &cities_idx, moves).is_none());
+                if moves == MoveSet::Full {
+                    println!("{}: {} {}", tsp_file, cost, counts);
+                } else {
+                    assert_eq!(counts.total(), counts.get(Reconnection::Exchange));
+                }
+
+                let mut sorted = cities_idx.clone();
+                sorted.sort();
+                assert_eq!(sorted, (0..instance.len()).collect::<Vec<usize>>());
+            }
+        }
+    }
+
+    #[test]
+    fn small() {
+        let x = [0, 10, 1, 11, 2];
+        let metric = FnMetric::new(5, |i, j| (x[i] as Cost - x[j] as Cost).abs());
+        let neighbors = NeighborLists::new(&metric, 2);
+        let mut cities_idx = vec![0, 1, 2, 3, 4];
+        super::local_optimum(
+            &mut NullObserver,
+            &metric,
+            &mut cities_idx,
+            &neighbors,
+            MoveSet::Full,
+            None,
+        );
+        assert_eq!(metric.tour_length(&cities_idx), 22);
+    }
+
+    #[test]
+    fn chain() {
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        let solution = Pipeline::new(Greedy)
+            .then(TwoOpt::new())
+            .then(OrOpt::new())
+            .then(ThreeOpt::new())
+            .solve(&instance);
+        let two_opt = Pipeline::new(Greedy).then(TwoOpt::new()).solve(&instance);
+        assert!(solution.cost <= two_opt.cost);
+    }
+
+    // Full 3-opt falls back to moves without reversed segments on asymmetric distances
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let initial = Pipeline::new(Greedy).solve(&metric);
+        let neighbors = NeighborLists::new(&metric, NEIGHBORS);
+
+        let mut cities_idx = initial.cities.clone();
+        let counts = super::local_optimum(
+            &mut NullObserver,
+            &metric,
+            &mut cities_idx,
+            &neighbors,
+            MoveSet::Full,
+            None,
+        );
+        assert_eq!(counts.total(), counts.get(Reconnection::Exchange));
+        assert!(metric.tour_length(&cities_idx) < initial.cost);
+        assert!(search_all(&metric, &cities_idx, MoveSet::OrThreeOpt).is_none());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::metric::Metric;
+use crate::tsplib::{parse_value, tokens, Instance, Lines, ParseError, ParseErrorKind};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// Tour in TSPLIB .tour format.
+// cities holds 0-based indexes of cities, which are node ids minus one in the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tour {
+    pub name: String,
+    pub comment: String,
+    pub cities: Vec<usize>,
+}
+
+impl Tour {
+    pub fn new(name: &str, cities: Vec<usize>) -> Tour {
+        Tour {
+            name: name.to_string(),
+            comment: String::new(),
+            cities,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(tour_file: P) -> Result<Tour, ParseError> {
+        let f = File::open(tour_file).map_err(|e| ParseError::new(0, 0, ParseErrorKind::Io(e)))?;
+        Tour::parse(BufReader::new(f))
+    }
+
+    pub fn parse<R: BufRead>(reader: R) -> Result<Tour, ParseError> {
+        let mut tour = Tour::default();
+        let mut lines = Lines::new(reader);
+        let mut dimension = None;
+        let mut section_found = false;
+
+        while let Some(line) = lines.next()? {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value, value_column) = match line.split_once(':') {
+                Some((key, value)) => (
+                    key.trim(),
+                    value.trim(),
+                    line.len() - value.trim_start().len() + 1,
+                ),
+                None => (line, "", line.len() + 1),
+            };
+
+            match key {
+                "NAME" => tour.name = value.to_string(),
+                "COMMENT" => {
+                    if !tour.comment.is_empty() {
+                        tour.comment.push('\n');
+                    }
+                    tour.comment.push_str(value);
+                }
+                "DIMENSION" => dimension = Some(parse_value(value, lines.line, value_column)?),
+                "TOUR_SECTION" => {
+                    tour.cities = tour_section(&mut lines, dimension)?;
+                    section_found = true;
+                }
+                "EOF" => break,
+                _ => {}
+            }
+        }
+
+        if !section_found {
+            return Err(ParseError::new(
+                lines.line,
+                1,
+                ParseErrorKind::MissingSection("TOUR_SECTION"),
+            ));
+        }
+
+        Ok(tour)
+    }
+
+    // Check that the tour visits every city of instance once, which the file alone
+    // can't tell without DIMENSION. Errors have no position in the file.
+    pub fn validate(&self, instance: &Instance) -> Result<(), ParseError> {
+        let error = |kind| Err(ParseError::new(0, 0, kind));
+        let mut seen = vec![false; instance.len()];
+        for city in self.cities.iter() {
+            match seen.get_mut(*city) {
+                None => return error(ParseErrorKind::UnknownNodeId(city + 1)),
+                Some(true) => return error(ParseErrorKind::DuplicateNodeId(city + 1)),
+                Some(seen) => *seen = true,
+            }
+        }
+        if self.cities.len() != instance.len() {
+            return error(ParseErrorKind::DimensionMismatch {
+                expected: instance.len(),
+                found: self.cities.len(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, tour_file: P) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(tour_file)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "NAME : {}", self.name)?;
+        for comment in self.comment.lines() {
+            writeln!(writer, "COMMENT : {}", comment)?;
+        }
+        writeln!(writer, "TYPE : TOUR")?;
+        writeln!(writer, "DIMENSION : {}", self.cities.len())?;
+        writeln!(writer, "TOUR_SECTION")?;
+        for city in self.cities.iter() {
+            writeln!(writer, "{}", city + 1)?;
+        }
+        writeln!(writer, "-1")?;
+        writeln!(writer, "EOF")
+    }
+}
+
+// Read node ids until -1
+fn tour_section<R: BufRead>(
+    lines: &mut Lines<R>,
+    dimension: Option<usize>,
+) -> Result<Vec<usize>, ParseError> {
+    let mut cities = vec![];
+    let mut seen = HashSet::new();
+
+    loop {
+        let line = match lines.next()? {
+            Some(line) => line,
+            None => return Err(ParseError::new(lines.line, 1, ParseErrorKind::Truncated)),
+        };
+
+        for (column, token) in tokens(&line) {
+            let id: i64 = parse_value(token, lines.line, column)?;
+            if id == -1 {
+                if let Some(dimension) = dimension {
+                    if cities.len() != dimension {
+                        return Err(ParseError::new(
+                            lines.line,
+                            column,
+                            ParseErrorKind::DimensionMismatch {
+                                expected: dimension,
+                                found: cities.len(),
+                            },
+                        ));
+                    }
+                }
+                return Ok(cities);
+            }
+
+            if id < 1 {
+                return Err(ParseError::new(
+                    lines.line,
+                    column,
+                    ParseErrorKind::BadNumber(token.to_string()),
+                ));
+            }
+            let id = id as usize;
+            if dimension.is_some_and(|d| id > d) {
+                return Err(ParseError::new(
+                    lines.line,
+                    column,
+                    ParseErrorKind::UnknownNodeId(id),
+                ));
+            }
+            if !seen.insert(id) {
+                return Err(ParseError::new(
+                    lines.line,
+                    column,
+                    ParseErrorKind::DuplicateNodeId(id),
+                ));
+            }
+            cities.push(id - 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::TSP_FILE_BERLIN52, observer::NullObserver};
+
+    #[test]
+    fn write_and_read() {
+        let mut tour = Tour::new("tiny.tour", vec![0, 3, 1, 2]);
+        tour.comment = "Length 10".to_string();
+
+        let mut buf = vec![];
+        tour.write(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert_eq!(
+            text,
+            "NAME : tiny.tour\nCOMMENT : Length 10\nTYPE : TOUR\nDIMENSION : 4\n\
+            TOUR_SECTION\n1\n4\n2\n3\n-1\nEOF\n"
+        );
+
+        assert_eq!(Tour::parse(text.as_bytes()).unwrap(), tour);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let file = std::env::temp_dir().join("tsp_save_and_load.tour");
+        let tour = Tour::new("berlin52.tour", (0..52).rev().collect());
+        tour.save(&file).unwrap();
+        assert_eq!(Tour::load(&file).unwrap(), tour);
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn parse() {
+        // Several ids in a line and no DIMENSION are allowed
+        let data = "NAME: t\nTYPE: TOUR\nTOUR_SECTION\n3 1\n2\n-1\n";
+        assert_eq!(Tour::parse(data.as_bytes()).unwrap().cities, vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn parse_error() {
+        let e = Tour::parse("DIMENSION: 3\nTOUR_SECTION\n1\n2\n-1\n".as_bytes()).unwrap_err();
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::DimensionMismatch {
+                expected: 3,
+                found: 2
+            }
+        ));
+
+        let e = Tour::parse("DIMENSION: 3\nTOUR_SECTION\n1 4\n".as_bytes()).unwrap_err();
+        assert!(matches!(e.kind, ParseErrorKind::UnknownNodeId(4)));
+        assert_eq!((e.line, e.column), (3, 3));
+
+        let e = Tour::parse("TOUR_SECTION\n1 2 1\n-1\n".as_bytes()).unwrap_err();
+        assert!(matches!(e.kind, ParseErrorKind::DuplicateNodeId(1)));
+
+        let e = Tour::parse("TOUR_SECTION\n1 2\n".as_bytes()).unwrap_err();
+        assert!(matches!(e.kind, ParseErrorKind::Truncated));
+
+        let e = Tour::parse("NAME: t\nEOF\n".as_bytes()).unwrap_err();
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::MissingSection("TOUR_SECTION")
+        ));
+    }
+
+    #[test]
+    fn validate() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let tour = Tour::new("berlin52.tour", (0..52).collect());
+        assert!(tour.validate(&instance).is_ok());
+
+        let e = Tour::new("t", vec![0, 1, 52])
+            .validate(&instance)
+            .unwrap_err();
+        assert!(matches!(e.kind, ParseErrorKind::UnknownNodeId(53)));
+
+        // Ids are checked before the length
+        let e = Tour::new("t", vec![0, 1, 0])
+            .validate(&instance)
+            .unwrap_err();
+        assert!(matches!(e.kind, ParseErrorKind::DuplicateNodeId(1)));
+
+        let e = Tour::new("t", (0..51).collect())
+            .validate(&instance)
+            .unwrap_err();
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::DimensionMismatch {
+                expected: 52,
+                found: 51
+            }
+        ));
+        assert_eq!(e.to_string(), "DIMENSION is 52 but 51 entries are found");
+
+        // Files without DIMENSION are only checked against the instance
+        let tour = Tour::parse(
+            "TOUR_SECTION
+1 2 3
+-1
+"
+            .as_bytes(),
+        )
+        .unwrap();
+        assert!(tour.validate(&instance).is_err());
+    }
+
+    // Optimal tour of berlin52 cannot be improved by 2-opt
+    #[test]
+    fn two_opt_from_tour() {
+        let data = "NAME : berlin52.opt.tour\nTYPE : TOUR\nDIMENSION : 52\nTOUR_SECTION\n\
+            1 49 32 45 19 41 8 9 10 43 33 51 11 52 14 13 47 26 27 28 12 25 4 6 15 5\n\
+            24 48 38 37 40 39 36 35 34 44 46 16 29 50 20 23 30 2 7 42 21 17 3 18 31 22\n\
+            -1\nEOF\n";
+        let mut tour = Tour::parse(data.as_bytes()).unwrap();
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        tour.validate(&instance).unwrap();
+        assert_eq!(instance.tour_length(&tour.cities), 7542);
+
+        crate::two_opt::solver(&mut NullObserver, &instance, &mut tour.cities, 0);
+        assert_eq!(instance.tour_length(&tour.cities), 7542);
+    }
+}
@@ -0,0 +1,771 @@
+use crate::matrix::DistanceMatrix;
+use crate::metric::{Cost, EdgeWeightType, Metric};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::{fmt, fs::File, path::Path};
+
+// Largest DIMENSION of instances with EDGE_WEIGHT_SECTION, whose distance matrix
+// has DIMENSION^2 entries and would not fit in memory much beyond this
+pub const MAX_EXPLICIT_DIMENSION: usize = 10_000;
+
+// Problem instance read from a TSPLIB file
+#[derive(Debug, Clone, Default)]
+pub struct Instance {
+    pub name: String,
+    // Value of the TYPE keyword such as TSP or ATSP
+    pub problem_type: String,
+    pub comment: String,
+    pub dimension: usize,
+    pub edge_weight_type: EdgeWeightType,
+    // Layout of EDGE_WEIGHT_SECTION such as FULL_MATRIX. Empty unless EDGE_WEIGHT_TYPE is EXPLICIT.
+    pub edge_weight_format: String,
+    // For EXPLICIT instances these come from DISPLAY_DATA_SECTION, if any,
+    // and are only used for plotting.
+    pub node_coords: Vec<(f64, f64)>,
+    // Third coordinate of 3D instances. Empty for 2D instances.
+    pub node_z: Vec<f64>,
+    // Distances given by EDGE_WEIGHT_SECTION
+    pub edge_weights: Option<DistanceMatrix>,
+}
+
+// Error returned when a TSPLIB file cannot be read.
+// `line` and `column` are 1-based and point at the offending token.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    Io(std::io::Error),
+    // A required keyword or section such as DIMENSION or NODE_COORD_SECTION is not found
+    MissingSection(&'static str),
+    // File or line ends before all values are read
+    Truncated,
+    BadNumber(String),
+    DuplicateNodeId(usize),
+    // Node or tour refers to a node id not in 1..=DIMENSION
+    UnknownNodeId(usize),
+    // Number of entries in a section differs from DIMENSION
+    DimensionMismatch { expected: usize, found: usize },
+    // Keyword value we can't handle such as EDGE_WEIGHT_FORMAT: FUNCTION
+    Unsupported(String),
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, column: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError { line, column, kind }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Line 0 means the file could not be read at all
+        if self.line > 0 {
+            write!(f, "line {}, column {}: ", self.line, self.column)?;
+        }
+        match &self.kind {
+            ParseErrorKind::Io(e) => write!(f, "{}", e),
+            ParseErrorKind::MissingSection(name) => write!(f, "{} is not found", name),
+            ParseErrorKind::Truncated => write!(f, "unexpected end of data"),
+            ParseErrorKind::BadNumber(token) => write!(f, "cannot parse {:?} as a number", token),
+            ParseErrorKind::DuplicateNodeId(id) => write!(f, "node {} appears twice", id),
+            ParseErrorKind::UnknownNodeId(id) => write!(f, "node {} does not exist", id),
+            ParseErrorKind::DimensionMismatch { expected, found } => write!(
+                f,
+                "DIMENSION is {} but {} entries are found",
+                expected, found
+            ),
+            ParseErrorKind::Unsupported(value) => write!(f, "{} is not supported", value),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Instance {
+    pub fn load<P: AsRef<Path>>(tsp_file: P) -> Result<Instance, ParseError> {
+        let f = File::open(tsp_file).map_err(|e| ParseError::new(0, 0, ParseErrorKind::Io(e)))?;
+        Instance::parse(BufReader::new(f))
+    }
+
+    pub fn parse<R: BufRead>(reader: R) -> Result<Instance, ParseError> {
+        let mut instance = Instance::default();
+        let mut lines = Lines::new(reader);
+        let mut dimension_found = false;
+        let mut coords_found = false;
+        let mut weights_found = false;
+
+        while let Some(line) = lines.next()? {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            // Both `KEY: value` and `KEY : value` appear in TSPLIB files
+            let (key, value, value_column) = match line.split_once(':') {
+                Some((key, value)) => (
+                    key.trim(),
+                    value.trim(),
+                    line.len() - value.trim_start().len() + 1,
+                ),
+                None => (line, "", line.len() + 1),
+            };
+
+            match key {
+                "NAME" => instance.name = value.to_string(),
+                "TYPE" => instance.problem_type = value.to_string(),
+                "COMMENT" => {
+                    // Some files have several COMMENT lines
+                    if !instance.comment.is_empty() {
+                        instance.comment.push('\n');
+                    }
+                    instance.comment.push_str(value);
+                }
+                "DIMENSION" => {
+                    instance.dimension = parse_value(value, lines.line, value_column)?;
+                    dimension_found = true;
+                }
+                "EDGE_WEIGHT_TYPE" => {
+                    instance.edge_weight_type = value.parse().map_err(|value| {
+                        ParseError::new(
+                            lines.line,
+                            value_column,
+                            ParseErrorKind::Unsupported(value),
+                        )
+                    })?;
+                }
+                "EDGE_WEIGHT_FORMAT" => instance.edge_weight_format = value.to_string(),
+                "NODE_COORD_SECTION" | "DISPLAY_DATA_SECTION" => {
+                    if !dimension_found {
+                        return Err(ParseError::new(
+                            lines.line,
+                            1,
+                            ParseErrorKind::MissingSection("DIMENSION"),
+                        ));
+                    }
+                    lines.node_section(&mut instance)?;
+                    coords_found = true;
+                }
+                "EDGE_WEIGHT_SECTION" => {
+                    if !dimension_found {
+                        return Err(ParseError::new(
+                            lines.line,
+                            1,
+                            ParseErrorKind::MissingSection("DIMENSION"),
+                        ));
+                    }
+                    lines.weight_section(&mut instance)?;
+                    weights_found = true;
+                }
+                "EOF" => break,
+                // Ignore keywords we don't use such as CAPACITY
+                _ => {}
+            }
+        }
+
+        if !dimension_found {
+            return Err(ParseError::new(
+                lines.line,
+                1,
+                ParseErrorKind::MissingSection("DIMENSION"),
+            ));
+        }
+        if instance.edge_weight_type == EdgeWeightType::Explicit {
+            if !weights_found {
+                return Err(ParseError::new(
+                    lines.line,
+                    1,
+                    ParseErrorKind::MissingSection("EDGE_WEIGHT_SECTION"),
+                ));
+            }
+        } else if !coords_found {
+            return Err(ParseError::new(
+                lines.line,
+                1,
+                ParseErrorKind::MissingSection("NODE_COORD_SECTION"),
+            ));
+        }
+
+        Ok(instance)
+    }
+
+    fn point(&self, i: usize) -> (f64, f64, f64) {
+        let (x, y) = self.node_coords[i];
+        (x, y, self.node_z.get(i).copied().unwrap_or(0.))
+    }
+}
+
+impl Metric for Instance {
+    fn len(&self) -> usize {
+        self.dimension
+    }
+
+    // Distance between i-th and j-th city (0-based) following EDGE_WEIGHT_TYPE
+    fn dist(&self, i: usize, j: usize) -> Cost {
+        match &self.edge_weights {
+            Some(weights) => weights.get(i, j),
+            None => self.edge_weight_type.distance(self.point(i), self.point(j)),
+        }
+    }
+}
+
+// Line reader counting line numbers for error messages
+pub(crate) struct Lines<R> {
+    lines: std::io::Lines<R>,
+    pub(crate) line: usize,
+    // Line read ahead by a section parser and returned again by next()
+    unread: Option<String>,
+}
+
+impl<R: BufRead> Lines<R> {
+    pub(crate) fn new(reader: R) -> Lines<R> {
+        Lines {
+            lines: reader.lines(),
+            line: 0,
+            unread: None,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Result<Option<String>, ParseError> {
+        if let Some(line) = self.unread.take() {
+            return Ok(Some(line));
+        }
+
+        match self.lines.next() {
+            Some(Ok(line)) => {
+                self.line += 1;
+                Ok(Some(line))
+            }
+            Some(Err(e)) => Err(ParseError::new(self.line + 1, 0, ParseErrorKind::Io(e))),
+            None => Ok(None),
+        }
+    }
+
+    // Read `id x y` or `id x y z` lines until a keyword line or end of file.
+    // Node id i is city i - 1 whatever order the lines are in, as in .tour files.
+    fn node_section(&mut self, instance: &mut Instance) -> Result<(), ParseError> {
+        let dimension = instance.dimension;
+        // DISPLAY_DATA_SECTION of EXPLICIT instances is always 2D
+        let is_3d = instance.edge_weight_type.is_3d();
+        let mut nodes = vec![];
+        let mut seen = HashSet::new();
+
+        while let Some(line) = self.next()? {
+            let tokens = tokens(&line);
+            if tokens.is_empty() {
+                continue;
+            }
+            if is_keyword(tokens[0].1) {
+                self.unread = Some(line);
+                break;
+            }
+            if tokens.len() < if is_3d { 4 } else { 3 } {
+                return Err(ParseError::new(
+                    self.line,
+                    line.len() + 1,
+                    ParseErrorKind::Truncated,
+                ));
+            }
+
+            let id: usize = parse_value(tokens[0].1, self.line, tokens[0].0)?;
+            // Lines past DIMENSION are reported as a mismatch below
+            if (id == 0 || id > dimension) && nodes.len() < dimension {
+                return Err(ParseError::new(
+                    self.line,
+                    tokens[0].0,
+                    ParseErrorKind::UnknownNodeId(id),
+                ));
+            }
+            if !seen.insert(id) {
+                return Err(ParseError::new(
+                    self.line,
+                    tokens[0].0,
+                    ParseErrorKind::DuplicateNodeId(id),
+                ));
+            }
+            let x = parse_value(tokens[1].1, self.line, tokens[1].0)?;
+            let y = parse_value(tokens[2].1, self.line, tokens[2].0)?;
+            let z = if is_3d {
+                parse_value(tokens[3].1, self.line, tokens[3].0)?
+            } else {
+                0.
+            };
+
+            nodes.push((id, x, y, z));
+        }
+
+        if nodes.len() != dimension {
+            // Running out of lines before the section is filled means the file is cut off
+            let kind = if self.unread.is_none() && nodes.len() < dimension {
+                ParseErrorKind::Truncated
+            } else {
+                ParseErrorKind::DimensionMismatch {
+                    expected: dimension,
+                    found: nodes.len(),
+                }
+            };
+            return Err(ParseError::new(self.line, 1, kind));
+        }
+
+        // Ids are 1..=dimension without duplicates here
+        nodes.sort_by_key(|(id, ..)| *id);
+        instance.node_coords = nodes.iter().map(|(_, x, y, _)| (*x, *y)).collect();
+        instance.node_z = if is_3d {
+            nodes.iter().map(|(.., z)| *z).collect()
+        } else {
+            vec![]
+        };
+
+        Ok(())
+    }
+
+    // Read EDGE_WEIGHT_SECTION, whose numbers can be wrapped at any place,
+    // and fill the distance matrix following EDGE_WEIGHT_FORMAT
+    fn weight_section(&mut self, instance: &mut Instance) -> Result<(), ParseError> {
+        let n = instance.dimension;
+        // Checked before the matrix is allocated from a DIMENSION nothing else bounds
+        if n > MAX_EXPLICIT_DIMENSION {
+            return Err(ParseError::new(
+                self.line,
+                1,
+                ParseErrorKind::Unsupported(format!("DIMENSION {} with EDGE_WEIGHT_SECTION", n)),
+            ));
+        }
+        let pairs = match weight_pairs(&instance.edge_weight_format, n) {
+            Some(pairs) => pairs,
+            None => {
+                let kind = if instance.edge_weight_format.is_empty() {
+                    ParseErrorKind::MissingSection("EDGE_WEIGHT_FORMAT")
+                } else {
+                    ParseErrorKind::Unsupported(instance.edge_weight_format.clone())
+                };
+                return Err(ParseError::new(self.line, 1, kind));
+            }
+        };
+
+        let mut matrix = DistanceMatrix::new(n);
+        let mut count = 0;
+
+        while count < pairs.len() {
+            let line = match self.next()? {
+                Some(line) => line,
+                None => {
+                    return Err(ParseError::new(self.line, 1, ParseErrorKind::Truncated));
+                }
+            };
+            let tokens = tokens(&line);
+            if tokens.first().is_some_and(|t| is_keyword(t.1)) {
+                self.unread = Some(line);
+                return Err(ParseError::new(
+                    self.line,
+                    1,
+                    ParseErrorKind::DimensionMismatch {
+                        expected: pairs.len(),
+                        found: count,
+                    },
+                ));
+            }
+
+            for (column, token) in tokens.into_iter().take(pairs.len() - count) {
+                let weight = parse_value(token, self.line, column)?;
+                let (i, j) = pairs[count];
+                matrix.set(i, j, weight);
+                // Triangular formats give only one side of symmetric matrix
+                if instance.edge_weight_format != "FULL_MATRIX" {
+                    matrix.set(j, i, weight);
+                }
+                count += 1;
+            }
+        }
+
+        instance.edge_weights = Some(matrix);
+
+        Ok(())
+    }
+}
+
+// Pairs of city indexes in the order their weights appear in EDGE_WEIGHT_SECTION
+fn weight_pairs(format: &str, n: usize) -> Option<Vec<(usize, usize)>> {
+    let mut pairs = vec![];
+
+    // Column-wise formats list the same pairs as the opposite row-wise format
+    // with the indexes swapped, which doesn't matter for a symmetric matrix.
+    match format {
+        "FULL_MATRIX" => {
+            for i in 0..n {
+                for j in 0..n {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        "UPPER_ROW" | "LOWER_COL" => {
+            for i in 0..n {
+                for j in i + 1..n {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        "LOWER_ROW" | "UPPER_COL" => {
+            for i in 0..n {
+                for j in 0..i {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        "UPPER_DIAG_ROW" | "LOWER_DIAG_COL" => {
+            for i in 0..n {
+                for j in i..n {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        "LOWER_DIAG_ROW" | "UPPER_DIAG_COL" => {
+            for i in 0..n {
+                for j in 0..=i {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        _ => return None,
+    }
+
+    Some(pairs)
+}
+
+// Split line by whitespace and return each token with its 1-based column
+pub(crate) fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = vec![];
+    let mut start = None;
+
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                tokens.push((s + 1, &line[s..i]));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s + 1, &line[s..]));
+    }
+
+    tokens
+}
+
+fn is_keyword(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+pub(crate) fn parse_value<T: std::str::FromStr>(
+    token: &str,
+    line: usize,
+    column: usize,
+) -> Result<T, ParseError> {
+    if token.is_empty() {
+        return Err(ParseError::new(line, column, ParseErrorKind::Truncated));
+    }
+    token
+        .parse::<T>()
+        .map_err(|_| ParseError::new(line, column, ParseErrorKind::BadNumber(token.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{TSP_FILE_BERLIN52, TSP_FILE_KROC100, TSP_FILE_TS225};
+
+    #[test]
+    fn berlin() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        assert_eq!(instance.name, "berlin52");
+        assert_eq!(instance.problem_type, "TSP");
+        assert_eq!(instance.comment, "52 locations in Berlin (Groetschel)");
+        assert_eq!(instance.dimension, 52);
+        assert_eq!(instance.edge_weight_type, EdgeWeightType::Euc2d);
+        assert_eq!(instance.node_coords[0], (565., 575.));
+        assert_eq!(instance.node_coords[51], (1740., 245.));
+    }
+
+    // ts225 and kroC100 write keys in `KEY : value` form
+    #[test]
+    fn spaced_colon() {
+        let instance = Instance::load(TSP_FILE_TS225).unwrap();
+        assert_eq!(instance.name, "ts225");
+        assert_eq!(instance.dimension, 225);
+        assert_eq!(instance.edge_weight_type, EdgeWeightType::Euc2d);
+        assert_eq!(instance.node_coords.len(), 225);
+
+        let instance = Instance::load(TSP_FILE_KROC100).unwrap();
+        assert_eq!(instance.edge_weight_type, EdgeWeightType::Euc2d);
+        assert_eq!(instance.node_coords.len(), 100);
+    }
+
+    #[test]
+    fn parse() {
+        let data = "NAME : tiny\nTYPE:TSP\nDIMENSION :3\nEDGE_WEIGHT_TYPE: EUC_2D\n\
+            NODE_COORD_SECTION\n1 0 0\n2 3.5 0\n3 0 4e1\nEOF\n";
+        let instance = Instance::parse(data.as_bytes()).unwrap();
+        assert_eq!(instance.name, "tiny");
+        assert_eq!(instance.problem_type, "TSP");
+        assert_eq!(instance.dimension, 3);
+        assert_eq!(instance.node_coords, vec![(0., 0.), (3.5, 0.), (0., 40.)]);
+    }
+
+    fn parse_error(data: &str) -> ParseError {
+        Instance::parse(data.as_bytes()).unwrap_err()
+    }
+
+    #[test]
+    fn truncated() {
+        let e = parse_error("NAME: tiny\nDIMENSION: 3\nNODE_COORD_SECTION\n1 0 0\n2 1 1\n");
+        assert!(matches!(e.kind, ParseErrorKind::Truncated));
+        assert_eq!(e.line, 5);
+
+        // Short coordinate line
+        let e = parse_error("DIMENSION: 2\nNODE_COORD_SECTION\n1 0 0\n2 1\nEOF\n");
+        assert!(matches!(e.kind, ParseErrorKind::Truncated));
+        assert_eq!((e.line, e.column), (4, 4));
+    }
+
+    #[test]
+    fn missing_section() {
+        let e = parse_error("NAME: tiny\nNODE_COORD_SECTION\n1 0 0\nEOF\n");
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::MissingSection("DIMENSION")
+        ));
+
+        let e = parse_error("NAME: tiny\nDIMENSION: 1\nEOF\n");
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::MissingSection("NODE_COORD_SECTION")
+        ));
+    }
+
+    #[test]
+    fn bad_number() {
+        let e = parse_error("DIMENSION: 2\nNODE_COORD_SECTION\n1 0 0\n2 1 1.0.0\nEOF\n");
+        assert!(matches!(e.kind, ParseErrorKind::BadNumber(ref t) if t == "1.0.0"));
+        assert_eq!((e.line, e.column), (4, 5));
+
+        let e = parse_error("DIMENSION: many\n");
+        assert!(matches!(e.kind, ParseErrorKind::BadNumber(_)));
+        assert_eq!((e.line, e.column), (1, 12));
+    }
+
+    #[test]
+    fn duplicate_node_id() {
+        let e = parse_error("DIMENSION: 2\nNODE_COORD_SECTION\n1 0 0\n1 1 1\nEOF\n");
+        assert!(matches!(e.kind, ParseErrorKind::DuplicateNodeId(1)));
+        assert_eq!((e.line, e.column), (4, 1));
+    }
+
+    // Coordinates are stored by node id, so tours refer to the same cities as in the file
+    #[test]
+    fn node_order() {
+        let instance = Instance::parse(
+            "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EUC_2D\nNODE_COORD_SECTION\n2 3 4\n3 0 4\n1 0 0\nEOF\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(instance.node_coords, vec![(0., 0.), (3., 4.), (0., 4.)]);
+        assert_eq!(instance.dist(0, 1), 5);
+
+        for id in ["0", "4"] {
+            let e = parse_error(&format!(
+                "DIMENSION: 2\nNODE_COORD_SECTION\n1 0 0\n{} 1 1\nEOF\n",
+                id
+            ));
+            assert!(matches!(e.kind, ParseErrorKind::UnknownNodeId(_)));
+            assert_eq!((e.line, e.column), (4, 1));
+        }
+    }
+
+    #[test]
+    fn dimension_mismatch() {
+        let e = parse_error("DIMENSION: 3\nNODE_COORD_SECTION\n1 0 0\n2 1 1\nEOF\n");
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::DimensionMismatch {
+                expected: 3,
+                found: 2
+            }
+        ));
+
+        let e = parse_error("DIMENSION: 1\nNODE_COORD_SECTION\n1 0 0\n2 1 1\n");
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::DimensionMismatch {
+                expected: 1,
+                found: 2
+            }
+        ));
+    }
+
+    // Same 4 x 4 symmetric matrix written in every EDGE_WEIGHT_FORMAT
+    const EXPLICIT_WEIGHTS: [(&str, &str); 9] = [
+        ("FULL_MATRIX", "0 1 2 3\n1 0 4 5\n2 4 0 6\n3 5 6 0"),
+        ("UPPER_ROW", "1 2 3\n4 5\n6"),
+        ("LOWER_ROW", "1\n2 4\n3 5 6"),
+        ("UPPER_DIAG_ROW", "0 1 2 3\n0 4 5\n0 6\n0"),
+        ("LOWER_DIAG_ROW", "0\n1 0\n2 4 0\n3 5 6 0"),
+        ("UPPER_COL", "1\n2 4\n3 5 6"),
+        ("LOWER_COL", "1 2 3\n4 5\n6"),
+        ("UPPER_DIAG_COL", "0\n1 0\n2 4 0\n3 5 6 0"),
+        ("LOWER_DIAG_COL", "0 1 2 3\n0 4 5\n0 6\n0"),
+    ];
+
+    #[test]
+    fn explicit() {
+        let expected = DistanceMatrix::from_fn(4, |i, j| {
+            [[0, 1, 2, 3], [1, 0, 4, 5], [2, 4, 0, 6], [3, 5, 6, 0]][i][j]
+        });
+
+        for (format, weights) in EXPLICIT_WEIGHTS.iter() {
+            let data = format!(
+                "NAME: four\nTYPE: TSP\nDIMENSION: 4\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+                EDGE_WEIGHT_FORMAT: {}\nEDGE_WEIGHT_SECTION\n{}\nEOF\n",
+                format, weights
+            );
+            let instance = Instance::parse(data.as_bytes()).unwrap();
+            assert_eq!(instance.edge_weight_format, *format);
+            assert_eq!(
+                instance.edge_weights.as_ref(),
+                Some(&expected),
+                "{}",
+                format
+            );
+            assert!(instance.node_coords.is_empty());
+            assert_eq!(instance.tour_length(&[0, 1, 2, 3]), 1 + 4 + 6 + 3);
+        }
+    }
+
+    #[test]
+    fn explicit_wrapped_lines() {
+        // Numbers of a row can be wrapped and several rows can share a line
+        let data = "DIMENSION: 4\nEDGE_WEIGHT_TYPE: EXPLICIT\nEDGE_WEIGHT_FORMAT: UPPER_ROW\n\
+            EDGE_WEIGHT_SECTION\n1 2\n3 4 5 6\nDISPLAY_DATA_SECTION\n\
+            1 0 0\n2 1 0\n3 1 1\n4 0 1\nEOF\n";
+        let instance = Instance::parse(data.as_bytes()).unwrap();
+        assert_eq!(instance.dist(2, 3), 6);
+        assert_eq!(instance.dist(3, 2), 6);
+        // Display data is kept for plotting but not used for distances
+        assert_eq!(instance.node_coords.len(), 4);
+        assert_eq!(instance.dist(0, 1), 1);
+    }
+
+    #[test]
+    fn explicit_errors() {
+        let e = parse_error(
+            "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\nEDGE_WEIGHT_FORMAT: UPPER_ROW\n\
+            EDGE_WEIGHT_SECTION\n1 2\nEOF\n",
+        );
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::DimensionMismatch {
+                expected: 3,
+                found: 2
+            }
+        ));
+
+        let e = parse_error(
+            "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\nEDGE_WEIGHT_FORMAT: UPPER_ROW\n\
+            EDGE_WEIGHT_SECTION\n1 2\n",
+        );
+        assert!(matches!(e.kind, ParseErrorKind::Truncated));
+
+        let e = parse_error(
+            "DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\nEDGE_WEIGHT_FORMAT: FUNCTION\n\
+            EDGE_WEIGHT_SECTION\n1 2 3\n",
+        );
+        assert!(matches!(e.kind, ParseErrorKind::Unsupported(ref f) if f == "FUNCTION"));
+
+        // A huge DIMENSION is rejected instead of allocating its matrix
+        let e = parse_error(
+            "DIMENSION: 4000000000\nEDGE_WEIGHT_TYPE: EXPLICIT\nEDGE_WEIGHT_FORMAT: FULL_MATRIX\n\
+            EDGE_WEIGHT_SECTION\n0 1\n1 0\n",
+        );
+        assert!(matches!(e.kind, ParseErrorKind::Unsupported(_)));
+        assert_eq!(e.line, 4);
+
+        let e = parse_error("DIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\nEOF\n");
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::MissingSection("EDGE_WEIGHT_SECTION")
+        ));
+    }
+
+    #[test]
+    fn edge_weight_type() {
+        let data = "DIMENSION: 2\nEDGE_WEIGHT_TYPE: EUC_3D\nNODE_COORD_SECTION\n\
+            1 0 0 0\n2 2 3 6\nEOF\n";
+        let instance = Instance::parse(data.as_bytes()).unwrap();
+        assert_eq!(instance.edge_weight_type, EdgeWeightType::Euc3d);
+        assert_eq!(instance.node_z, vec![0., 6.]);
+        assert_eq!(instance.dist(0, 1), 7);
+
+        let data = "DIMENSION: 2\nEDGE_WEIGHT_TYPE: MAN_2D\nNODE_COORD_SECTION\n\
+            1 0 0\n2 2 3\nEOF\n";
+        let instance = Instance::parse(data.as_bytes()).unwrap();
+        assert_eq!(instance.dist(0, 1), 5);
+
+        // Coordinate lines of 3D instances need z
+        let e = parse_error("DIMENSION: 1\nEDGE_WEIGHT_TYPE: MAX_3D\nNODE_COORD_SECTION\n1 0 0\n");
+        assert!(matches!(e.kind, ParseErrorKind::Truncated));
+
+        let e = parse_error("DIMENSION: 1\nEDGE_WEIGHT_TYPE : XRAY1\n");
+        assert!(matches!(e.kind, ParseErrorKind::Unsupported(ref t) if t == "XRAY1"));
+        assert_eq!((e.line, e.column), (2, 20));
+    }
+
+    // EUC_2D distances are rounded to the nearest integer, which gives
+    // the published optimum 7542 for the optimal tour of berlin52
+    #[test]
+    fn berlin_optimum() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let optimal_tour = [
+            1, 49, 32, 45, 19, 41, 8, 9, 10, 43, 33, 51, 11, 52, 14, 13, 47, 26, 27, 28, 12, 25, 4,
+            6, 15, 5, 24, 48, 38, 37, 40, 39, 36, 35, 34, 44, 46, 16, 29, 50, 20, 23, 30, 2, 7, 42,
+            21, 17, 3, 18, 31, 22,
+        ];
+        let cities_idx = optimal_tour.iter().map(|id| id - 1).collect::<Vec<usize>>();
+        assert_eq!(instance.tour_length(&cities_idx), 7542);
+    }
+
+    // Instances with less than three cities are valid
+    #[test]
+    fn tiny() {
+        let coords = ["1 0 0", "2 3 4"];
+        for dimension in 0..=2 {
+            let data = format!(
+                "DIMENSION: {}\nEDGE_WEIGHT_TYPE: EUC_2D\nNODE_COORD_SECTION\n{}\nEOF\n",
+                dimension,
+                coords[..dimension].join("\n")
+            );
+            let instance = Instance::parse(data.as_bytes()).unwrap();
+            assert_eq!(instance.len(), dimension);
+            assert_eq!(
+                instance.tour_length(&(0..dimension).collect::<Vec<usize>>()),
+                10 * (dimension as Cost / 2)
+            );
+        }
+    }
+
+    #[test]
+    fn io_error() {
+        let e = Instance::load("no_such_file.tsp").unwrap_err();
+        assert!(matches!(e.kind, ParseErrorKind::Io(_)));
+    }
+}
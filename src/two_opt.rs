@@ -1,22 +1,315 @@
-use rand::{thread_rng, Rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::common::{distance, total_distance};
-use std::fs::File;
-use std::io::Write;
+use crate::metric::{Cost, Metric};
+use crate::neighbors::NeighborLists;
+use crate::observer::{Event, Observer};
+use crate::solver::LocalSearch;
+use std::time::Instant;
 
-pub fn solver(
-    gp: &mut std::process::Child,
-    visit_cities: &mut Vec<(f32, f32)>,
-    cities_idx: &mut Vec<usize>,
-) -> (Vec<(f32, f32)>, Vec<usize>) {
-    let mut last_update_idx = 0;
-    let city_len = visit_cities.len();
+// Number of iteration
+pub const ITERATIONS: usize = 10_000_000;
+
+// Number of nearest neighbors tried as new edges by default
+pub const NEIGHBORS: usize = 10;
+
+// How TwoOpt chooses moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    // Apply the first improving move found
+    #[default]
+    FirstImprovement,
+    // Apply the most improving move among all candidates
+    BestImprovement,
+    // Try ITERATIONS random pairs of edges
+    Random,
+}
+
+// Local search swapping two edges while it shortens the tour.
+// FirstImprovement and BestImprovement only try new edges to the nearest neighbors of cities,
+// then scan all pairs of edges. So the tour is always a 2-opt local optimum when they return
+// unless deadline has passed.
+#[derive(Debug, Clone)]
+pub struct TwoOpt {
+    strategy: Strategy,
+    neighbors: usize,
+    // Neighbor lists kept between prepare and finish
+    prepared: Option<NeighborLists>,
+}
+
+impl Default for TwoOpt {
+    fn default() -> TwoOpt {
+        TwoOpt {
+            strategy: Strategy::default(),
+            neighbors: NEIGHBORS,
+            prepared: None,
+        }
+    }
+}
+
+impl TwoOpt {
+    pub fn new() -> TwoOpt {
+        TwoOpt::default()
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> TwoOpt {
+        self.strategy = strategy;
+        self
+    }
+
+    // Number of nearest neighbors of each city tried as new edges
+    pub fn neighbors(mut self, k: usize) -> TwoOpt {
+        self.neighbors = k;
+        self
+    }
+}
+
+impl LocalSearch for TwoOpt {
+    fn improve(
+        &mut self,
+        metric: &dyn Metric,
+        cities_idx: &mut [usize],
+        deadline: Option<Instant>,
+        rng: &mut StdRng,
+        observer: &mut dyn Observer,
+    ) {
+        if self.strategy == Strategy::Random {
+            // Move deltas assume distances are the same in both directions
+            if metric.is_symmetric() {
+                solver_with(observer, metric, cities_idx, rng, deadline);
+            }
+            return;
+        }
+
+        let neighbors = NeighborLists::prepared_or_new(&self.prepared, metric, self.neighbors);
+        local_optimum(
+            observer,
+            metric,
+            cities_idx,
+            &neighbors,
+            self.strategy == Strategy::BestImprovement,
+            deadline,
+        );
+    }
+
+    fn prepare(&mut self, metric: &dyn Metric) {
+        self.prepared = Some(NeighborLists::new(metric, self.neighbors));
+    }
+
+    fn finish(&mut self) {
+        self.prepared = None;
+    }
+}
+
+// 2-opt move replacing edges (a, succ a) and (b, succ b) with (a, b) and (succ a, succ b),
+// which is done by reversing the part of the cycle from position from to position to.
+// The part may wrap around the end of cities_idx.
+#[derive(Debug, Clone, Copy)]
+struct Move {
+    delta: Cost,
+    from: usize,
+    to: usize,
+}
+
+// Apply improving moves until none remains.
+// Moves found with neighbor lists are applied first since they are cheap to find,
+// then all pairs of edges are checked to make sure the tour is a local optimum.
+// Tours of asymmetric metrics are left unchanged since reversing a segment also changes
+// the length of its edges, which the deltas ignore.
+pub fn local_optimum<M: Metric + ?Sized>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    neighbors: &NeighborLists,
+    best_improvement: bool,
+    deadline: Option<Instant>,
+) {
+    let city_len = cities_idx.len();
+    if city_len < 4 || !neighbors.is_symmetric() {
+        return;
+    }
+
+    // Position of each city in cities_idx
+    let mut pos = vec![0; city_len];
+    for (i, city) in cities_idx.iter().enumerate() {
+        pos[*city] = i;
+    }
+
+    // City from which the next search starts. First improvement resumes where
+    // the last move was found instead of scanning the same cities again.
+    let mut start = 0;
+
+    loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return;
+        }
+
+        let found = match search_neighbors(
+            metric,
+            cities_idx,
+            &pos,
+            neighbors,
+            best_improvement,
+            &mut start,
+        ) {
+            Some(m) => Some(m),
+            None => search_all(metric, cities_idx, best_improvement),
+        };
+
+        match found {
+            Some(m) => apply(observer, cities_idx, &mut pos, m),
+            // Local optimum
+            None => return,
+        }
+    }
+}
+
+// Find an improving move adding an edge between a city and one of its neighbors
+fn search_neighbors<M: Metric + ?Sized>(
+    metric: &M,
+    cities_idx: &[usize],
+    pos: &[usize],
+    neighbors: &NeighborLists,
+    best_improvement: bool,
+    start: &mut usize,
+) -> Option<Move> {
+    let city_len = cities_idx.len();
+    let succ = |city: usize| cities_idx[(pos[city] + 1) % city_len];
+    let pred = |city: usize| cities_idx[(pos[city] + city_len - 1) % city_len];
+
+    let mut best: Option<Move> = None;
+
+    for step in 0..city_len {
+        let a = (*start + step) % city_len;
+        let (pa, sa) = (pred(a), succ(a));
+
+        for c in neighbors.get(a).iter().copied() {
+            let d_ac = metric.dist(a, c);
+            // A new edge longer than both removed edges at a cannot make the tour shorter
+            // since every improving move has a new edge shorter than a removed one.
+            if d_ac >= metric.dist(a, sa) && d_ac >= metric.dist(pa, a) {
+                break;
+            }
+
+            // Replace (a, sa) and (c, sc) with (a, c) and (sa, sc)
+            let sc = succ(c);
+            if c != sa && sc != a {
+                let delta = d_ac + metric.dist(sa, sc) - metric.dist(a, sa) - metric.dist(c, sc);
+                if delta < 0 && best.is_none_or(|m| delta < m.delta) {
+                    best = Some(Move {
+                        delta,
+                        from: pos[sa],
+                        to: pos[c],
+                    });
+                }
+            }
+
+            // Replace (pa, a) and (pc, c) with (a, c) and (pa, pc)
+            let pc = pred(c);
+            if c != pa && pc != a {
+                let delta = d_ac + metric.dist(pa, pc) - metric.dist(pa, a) - metric.dist(pc, c);
+                if delta < 0 && best.is_none_or(|m| delta < m.delta) {
+                    best = Some(Move {
+                        delta,
+                        from: pos[a],
+                        to: pos[pc],
+                    });
+                }
+            }
+
+            if best.is_some() && !best_improvement {
+                *start = a;
+                return best;
+            }
+        }
+    }
+
+    best
+}
+
+// Find an improving move among all pairs of edges
+fn search_all<M: Metric + ?Sized>(
+    metric: &M,
+    cities_idx: &[usize],
+    best_improvement: bool,
+) -> Option<Move> {
+    let city_len = cities_idx.len();
+    let mut best: Option<Move> = None;
+
+    for i in 0..city_len - 2 {
+        let (a, sa) = (cities_idx[i], cities_idx[i + 1]);
+        for j in i + 2..city_len {
+            // Edges sharing a city
+            if i == 0 && j == city_len - 1 {
+                continue;
+            }
+            let (b, sb) = (cities_idx[j], cities_idx[(j + 1) % city_len]);
+
+            let delta =
+                metric.dist(a, b) + metric.dist(sa, sb) - metric.dist(a, sa) - metric.dist(b, sb);
+            if delta < 0 && best.is_none_or(|m| delta < m.delta) {
+                best = Some(Move {
+                    delta,
+                    from: i + 1,
+                    to: j,
+                });
+                if !best_improvement {
+                    return best;
+                }
+            }
+        }
+    }
+
+    best
+}
+
+fn apply(observer: &mut dyn Observer, cities_idx: &mut [usize], pos: &mut [usize], m: Move) {
+    // Reversing the rest of the cycle gives the same cycle in the opposite direction
+    let (i, j) = if m.from <= m.to {
+        (m.from, m.to)
+    } else {
+        (m.to + 1, m.from - 1)
+    };
+
+    cities_idx[i..=j].reverse();
+    for (k, city) in cities_idx[i..=j].iter().enumerate() {
+        pos[*city] = i + k;
+    }
+
+    observer.notify(&Event::TwoOptMove { i, j, cities_idx });
+}
+
+// Improve the cycle given by cities_idx in place by swapping edges.
+// Edges are chosen by random numbers from seed so the same seed gives the same tour.
+pub fn solver<M: Metric + ?Sized>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    seed: u64,
+) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    solver_with(observer, metric, cities_idx, &mut rng, None);
+}
+
+// Same as solver but picks edges with rng and stops early once deadline has passed
+pub fn solver_with<M: Metric + ?Sized, R: Rng>(
+    observer: &mut dyn Observer,
+    metric: &M,
+    cities_idx: &mut [usize],
+    rng: &mut R,
+    deadline: Option<Instant>,
+) {
+    let city_len = cities_idx.len();
+    if city_len < 4 {
+        return;
+    }
 
     // Swap
-    let mut rng = thread_rng();
-    // Number of iteration
-    let limit = 10_000_000;
-    for k in 0..limit {
+    for k in 0..ITERATIONS {
+        // Reading the clock every iteration is slow
+        if k % 1024 == 0 && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break;
+        }
+
         let mut i = rng.gen_range(0..city_len);
         let mut j = rng.gen_range(0..city_len);
 
@@ -29,88 +322,137 @@ pub fn solver(
 
         // Current distance
         let d1 =
-            distance(visit_cities[x], visit_cities[i]) + distance(visit_cities[j], visit_cities[y]);
+            metric.dist(cities_idx[x], cities_idx[i]) + metric.dist(cities_idx[j], cities_idx[y]);
         // Distance after swapped
         let d2 =
-            distance(visit_cities[x], visit_cities[j]) + distance(visit_cities[i], visit_cities[y]);
+            metric.dist(cities_idx[x], cities_idx[j]) + metric.dist(cities_idx[i], cities_idx[y]);
 
         if d1 > d2 && x != j && y != i {
-            last_update_idx = k;
+            cities_idx[i..=j].reverse();
 
-            if i == 0 && j == city_len - 1 {
-                visit_cities.swap(i, j);
-                cities_idx.swap(i, j);
-            } else {
-                let v = visit_cities.clone();
-                let c = cities_idx.clone();
-                for (l, m) in (i..=j).enumerate() {
-                    visit_cities[m] = v[j - l];
-                    cities_idx[m] = c[j - l]
-                }
-            }
+            observer.notify(&Event::TwoOptMove { i, j, cities_idx });
+        }
+    }
+}
 
-            #[cfg(feature = "plot")]
-            {
-                let mut v = visit_cities.clone();
-                v.push(v[0]);
-                let new = total_distance(&v);
-                // println!("[{}] {}", limit, new);
-
-                let mut edges = vec![];
-
-                for i in 0..city_len - 1 {
-                    edges.push(vec![
-                        visit_cities[i].0,
-                        visit_cities[i].1,
-                        visit_cities[i + 1].0,
-                        visit_cities[i + 1].1,
-                    ]);
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{random_asymmetric, TSP_FILE_BERLIN52, TSP_FILE_KROC100},
+        greedy::Greedy,
+        metric::FnMetric,
+        nearest_neighbor::NearestNeighbor,
+        observer::NullObserver,
+        solver::ConstructionHeuristic,
+        tsplib::Instance,
+    };
+    use std::time::Duration;
 
-                // Connect start and end city to make cycle
-                edges.push(vec![
-                    visit_cities[city_len - 1].0,
-                    visit_cities[city_len - 1].1,
-                    visit_cities[0].0,
-                    visit_cities[0].1,
-                ]);
+    #[test]
+    fn seeded() {
+        let instance = Instance::load(TSP_FILE_BERLIN52).unwrap();
+        let initial = Greedy.construct(&instance, &mut NullObserver);
 
-                plot(gp, &mut edges);
-            }
-        }
-    }
+        let mut cities_idx = initial.clone();
+        let deadline = Instant::now() + Duration::from_millis(200);
+        TwoOpt::new().improve(
+            &instance,
+            &mut cities_idx,
+            Some(deadline),
+            &mut StdRng::seed_from_u64(7),
+            &mut NullObserver,
+        );
+        assert!(instance.tour_length(&cities_idx) <= instance.tour_length(&initial));
 
-    // Connect start and end city to make cycle
-    visit_cities.push(visit_cities[0]);
-    cities_idx.push(cities_idx[0]);
+        // Same seed gives the same tour
+        let mut a = initial.clone();
+        let mut b = initial;
+        solver(&mut NullObserver, &instance, &mut a, 7);
+        solver(&mut NullObserver, &instance, &mut b, 7);
+        assert_eq!(a, b);
+    }
 
-    // println!("Last update index: {}", last_update_idx);
+    #[test]
+    fn local_optimum() {
+        for tsp_file in [TSP_FILE_BERLIN52, TSP_FILE_KROC100] {
+            let instance = Instance::load(tsp_file).unwrap();
+            let initial = NearestNeighbor.construct(&instance, &mut NullObserver);
 
-    (visit_cities.to_vec(), cities_idx.to_vec())
-}
+            for strategy in [Strategy::FirstImprovement, Strategy::BestImprovement] {
+                // Even a single neighbor gives a local optimum thanks to the final scan
+                for k in [1, NEIGHBORS] {
+                    let mut cities_idx = initial.clone();
+                    TwoOpt::new().strategy(strategy).neighbors(k).improve(
+                        &instance,
+                        &mut cities_idx,
+                        None,
+                        &mut StdRng::seed_from_u64(0),
+                        &mut NullObserver,
+                    );
 
-fn plot(gp: &mut std::process::Child, edges: &mut Vec<Vec<f32>>) {
-    let cmd = "plot 'cities.txt' with point pointtype 7 pointsize 2 linecolor rgb 'black', \
-    '-' using 1:2:($3-$1):($4-$2) with vectors lw 3 linetype 1 linecolor rgb 'cyan' nohead\n";
+                    assert!(instance.tour_length(&cities_idx) < instance.tour_length(&initial));
+                    assert!(search_all(&instance, &cities_idx, false).is_none());
 
-    gp.stdin
-        .as_mut()
-        .unwrap()
-        .write_all(cmd.as_bytes())
-        .unwrap();
+                    let mut sorted = cities_idx.clone();
+                    sorted.sort();
+                    assert_eq!(sorted, (0..instance.len()).collect::<Vec<usize>>());
+                }
+            }
+        }
+    }
 
-    for edge in edges.iter() {
-        let cmd = format!("{} {} {} {}\n", edge[0], edge[1], edge[2], edge[3]);
-        // let cmd: &str = &cmd;
+    // Tiny tours where all moves share a city or wrap around the end
+    #[test]
+    fn small() {
+        let x = [0, 10, 1, 11, 2];
+        let metric = FnMetric::new(5, |i, j| (x[i] as Cost - x[j] as Cost).abs());
+        let mut cities_idx = vec![0, 1, 2, 3, 4];
+        let neighbors = NeighborLists::new(&metric, 2);
+        super::local_optimum(
+            &mut NullObserver,
+            &metric,
+            &mut cities_idx,
+            &neighbors,
+            false,
+            None,
+        );
+        assert!(search_all(&metric, &cities_idx, false).is_none());
+        assert_eq!(metric.tour_length(&cities_idx), 22);
 
-        gp.stdin
-            .as_mut()
-            .unwrap()
-            .write_all(cmd.as_bytes())
-            .unwrap();
+        let mut cities_idx = vec![2, 0, 1];
+        super::local_optimum(
+            &mut NullObserver,
+            &metric,
+            &mut cities_idx,
+            &neighbors,
+            true,
+            None,
+        );
+        assert_eq!(cities_idx, vec![2, 0, 1]);
     }
-    // End data input
-    gp.stdin.as_mut().unwrap().write_all(b"e\n").unwrap();
 
-    std::thread::sleep(std::time::Duration::from_millis(20));
+    // Deltas are wrong for asymmetric distances, so the tour is kept instead of
+    // taking moves forever
+    #[test]
+    fn asymmetric() {
+        let metric = random_asymmetric(30, 0);
+        let initial = Greedy.construct(&metric, &mut NullObserver);
+
+        for strategy in [
+            Strategy::FirstImprovement,
+            Strategy::BestImprovement,
+            Strategy::Random,
+        ] {
+            let mut cities_idx = initial.clone();
+            TwoOpt::new().strategy(strategy).improve(
+                &metric,
+                &mut cities_idx,
+                None,
+                &mut StdRng::seed_from_u64(0),
+                &mut NullObserver,
+            );
+            assert_eq!(cities_idx, initial);
+        }
+    }
 }